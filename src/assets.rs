@@ -1,6 +1,7 @@
 use std::path::Path;
 use fastly::http::StatusCode;
-use fastly::{Error, mime, Response};
+use fastly::{mime, Response};
+use crate::error::Error;
 use rust_embed::RustEmbed;
 use std::ffi::OsStr;
 
@@ -36,15 +37,27 @@ pub fn file_mimetype(filename: &str, default: mime::Mime) -> mime::Mime {
 }
 
 pub fn serve(path: &str, mt: mime::Mime) -> Result<Response, Error> {
-    let not_found = Ok(Response::from_status(StatusCode::NOT_FOUND)
-        .with_content_type(mime::TEXT_HTML_UTF_8)
-        .with_body("E_NOTFOUND"));
-
     match Asset::get(path) {
         Some(asset) => Ok(Response::from_status(StatusCode::OK)
             .with_body_octet_stream(asset.data.as_ref())
             .with_content_type(mt)),
 
-        None => not_found,
+        None => Err(Error::NotFound(format!("Asset {} not found", path))),
+    }
+}
+
+/// Swagger UI files referring to the base path through a `{{BASE_PATH}}` placeholder.
+const TEMPLATED: [&str; 2] = ["/index.html", "/swagger-initializer.js"];
+
+/// Serves a file from the swagger-ui folder, if it exists.
+pub fn serve_swagger_ui(path: &str, base_path: &str) -> Option<Response> {
+    let asset = Asset::get(format!("swagger-ui{path}").as_str())?;
+    let resp = Response::from_status(StatusCode::OK)
+        .with_content_type(file_mimetype(path, mime::APPLICATION_OCTET_STREAM));
+
+    if TEMPLATED.contains(&path) {
+        let body = String::from_utf8_lossy(asset.data.as_ref()).replace("{{BASE_PATH}}", base_path);
+        return Some(resp.with_body(body));
     }
+    Some(resp.with_body_octet_stream(asset.data.as_ref()))
 }
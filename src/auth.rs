@@ -1,8 +1,44 @@
 use fastly::http::StatusCode;
-use fastly::{Error, mime, Request, Response};
-use serde_json::{json, to_string_pretty};
-use regex_lite::Regex;
+use fastly::{mime, Request, Response};
+use crate::client_cert;
+use crate::compat;
+use crate::format;
+use crate::error::Error;
+use serde_json::{json, to_string_pretty, Value};
+use crate::router::Params;
 use base64::{Engine as _, engine::general_purpose};
+use fastly_shared::ClientCertVerifyResult;
+
+/// httpbin answers failed authentication with a challenge and an empty body.
+fn compat_unauthorized(challenge: &str) -> Response {
+    Response::from_status(StatusCode::UNAUTHORIZED)
+        .with_content_type(mime::TEXT_HTML_UTF_8)
+        .with_header("www-authenticate", challenge)
+}
+
+/// Basic auth as httpbin does it: the challenge on every failure, and the
+/// user in the body on success.
+fn compat_basic_auth(req: &Request, params: &Params) -> Response {
+    let credentials = req.get_header("authorization")
+        .and_then(|auth| auth.to_str().ok())
+        .and_then(|auth| auth.strip_prefix("Basic "))
+        .and_then(|enc| general_purpose::STANDARD.decode(enc).ok())
+        .and_then(|dec| String::from_utf8(dec).ok());
+    let expected = params.get("user").zip(params.get("passwd")).map(|(user, pwd)| format!("{}:{}", user, pwd));
+
+    match (credentials, expected) {
+        (Some(credentials), Some(expected)) if credentials == expected => {
+            let resp = json!({
+                "authenticated": true,
+                "user": params.get("user"),
+            });
+            Response::from_status(StatusCode::OK)
+                .with_content_type(mime::APPLICATION_JSON)
+                .with_body(to_string_pretty(&resp).unwrap_or_default())
+        },
+        _ => compat_unauthorized("Basic realm=\"Fake Realm\""),
+    }
+}
 
 #[utoipa::path(
     get,
@@ -18,7 +54,10 @@ use base64::{Engine as _, engine::general_purpose};
     )
 )]
 /// Prompts the user for authorization using HTTP Basic Auth
-pub fn basic_auth(req: &Request) -> Result<Response, Error> {
+pub fn basic_auth(req: &Request, params: &Params) -> Result<Response, Error> {
+    if compat::enabled(req) {
+        return Ok(compat_basic_auth(req, params));
+    }
     let unauthorized = Ok(Response::from_status(StatusCode::UNAUTHORIZED)
         .with_content_type(mime::APPLICATION_JSON));
 
@@ -37,22 +76,17 @@ pub fn basic_auth(req: &Request) -> Result<Response, Error> {
     }
     let (given_user, given_pwd) = (credentials[0], credentials[1]);
 
-    let caps = Regex::new(r"/basic-auth/(\w+)/(\w+)$")?
-        .captures(req.get_path());
-    if caps.is_none() {
-        return unauthorized;
-    }
-
-    let c = caps.unwrap();
-    let user = c.get(1).unwrap().as_str();
-    let pwd = c.get(2).unwrap().as_str();
+    let (user, pwd) = match (params.get("user"), params.get("passwd")) {
+        (Some(user), Some(pwd)) => (user, pwd),
+        _ => return unauthorized,
+    };
 
     if given_user == user && given_pwd == pwd {
         return Ok(Response::from_status(StatusCode::OK)
             .with_content_type(mime::APPLICATION_JSON));
     }
 
-    return unauthorized;
+    unauthorized
 }
 
 #[utoipa::path(
@@ -65,9 +99,12 @@ pub fn basic_auth(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Prompts the user for authorization using bearer authentication.
-pub fn bearer(req: &Request) -> Result<Response, Error> {
-    let unauthorized = Ok(Response::from_status(StatusCode::UNAUTHORIZED)
-        .with_content_type(mime::APPLICATION_JSON));
+pub fn bearer(req: &Request, _: &Params) -> Result<Response, Error> {
+    let unauthorized = match compat::enabled(req) {
+        true => Ok(compat_unauthorized("Bearer")),
+        false => Ok(Response::from_status(StatusCode::UNAUTHORIZED)
+            .with_content_type(mime::APPLICATION_JSON)),
+    };
 
     match req.get_header("authorization") {
         Some(auth) => {
@@ -90,17 +127,68 @@ pub fn bearer(req: &Request) -> Result<Response, Error> {
 }
 
 
+fn verify_result_name(result: &ClientCertVerifyResult) -> &'static str {
+    match result {
+        ClientCertVerifyResult::Ok => "ok",
+        ClientCertVerifyResult::BadCertificate => "bad_certificate",
+        ClientCertVerifyResult::CertificateRevoked => "certificate_revoked",
+        ClientCertVerifyResult::CertificateExpired => "certificate_expired",
+        ClientCertVerifyResult::UnknownCa => "unknown_ca",
+        ClientCertVerifyResult::CertificateMissing => "certificate_missing",
+        ClientCertVerifyResult::CertificateUnknown => "certificate_unknown",
+    }
+}
+
+/// Describes the certificate the client presented, given its PEM and how
+/// Fastly's mTLS verification went. Services without mTLS verify every
+/// connection as Ok, so a certificate is needed as well.
+fn client_cert_to_value(pem: Option<&str>, verified: Option<ClientCertVerifyResult>) -> Result<Value, Error> {
+    let pem = match (pem.filter(|pem| !pem.trim().is_empty()), &verified) {
+        (None, _) | (_, Some(ClientCertVerifyResult::CertificateMissing)) =>
+            return Err(Error::SslCertificateRequired(String::from("No client certificate was presented"))),
+        (Some(pem), _) => pem,
+    };
+    if let Some(result) = verified.as_ref().filter(|r| !matches!(r, ClientCertVerifyResult::Ok)) {
+        return Err(Error::SslCertificate(format!("The client certificate was rejected: {}", verify_result_name(result))));
+    }
+    let certificate = client_cert::from_pem(pem)
+        .and_then(|der| client_cert::describe(&der))
+        .map_err(|e| Error::SslCertificate(format!("The client certificate could not be parsed: {}", e)))?;
+
+    Ok(json!({
+        "presented": true,
+        "verification": verified.as_ref().map(verify_result_name),
+        "certificate": certificate,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/client-cert",
+    tag = "Auth",
+    responses(
+        (status = 200, description = "The client certificate presented over mutual TLS: subject, issuer, SANs, validity and fingerprint", content_type = "application/json"),
+        (status = 495, description = "The client certificate failed verification or could not be parsed", content_type = "application/problem+json"),
+        (status = 496, description = "No client certificate was presented", content_type = "application/problem+json")
+    )
+)]
+/// Returns the client certificate presented over mutual TLS.
+pub fn client_cert(req: &Request, _: &Params) -> Result<Response, Error> {
+    let resp = client_cert_to_value(req.get_tls_raw_client_certificate(), req.get_tls_client_cert_verify_result())?;
+    format::echo(req, &resp)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use serde_json::Value;
+    use crate::client_cert::test::CLIENT_PEM;
 
     #[test]
     fn test_basic_auth_success() {
         let req = &Request::from_client()
             .with_path("/basic-auth/foo/bar")
             .with_header("authorization", "Basic Zm9vOmJhcg=="); // echo -n foo:bar | base64
-        let resp = basic_auth(req);
+        let resp = basic_auth(req, &Params::from([("user", "foo"), ("passwd", "bar")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -112,7 +200,7 @@ mod test {
         let req = &Request::from_client()
             .with_path("/basic-auth/foo/bar")
             .with_header("authorization", "Basic foo");
-        let resp = basic_auth(req);
+        let resp = basic_auth(req, &Params::from([("user", "foo"), ("passwd", "bar")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::UNAUTHORIZED);
@@ -123,7 +211,7 @@ mod test {
     fn test_basic_auth_no_authorization() {
         let req = &Request::from_client()
             .with_path("/basic-auth/foo/bar");
-        let resp = basic_auth(req);
+        let resp = basic_auth(req, &Params::from([("user", "foo"), ("passwd", "bar")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::UNAUTHORIZED);
@@ -136,7 +224,7 @@ mod test {
         let req = &Request::from_client()
             .with_header("authorization", "Bearer Zm9vOmZvbwo=")
             .with_path("/basic-auth/foo/bar");
-        let resp = basic_auth(req);
+        let resp = basic_auth(req, &Params::from([("user", "foo"), ("passwd", "bar")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::UNAUTHORIZED);
@@ -148,7 +236,7 @@ mod test {
         let req = &Request::from_client()
             .with_path("/bearer")
             .with_header("authorization", "Bearer foo");
-        let resp = bearer(req);
+        let resp = bearer(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -164,10 +252,74 @@ mod test {
     fn test_bearer_failure() {
         let req = &Request::from_client()
             .with_path("/bearer"); // No authorization header
-        let resp = bearer(req);
+        let resp = bearer(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::UNAUTHORIZED);
         assert_eq!(resp.get_content_type(), Some(mime::APPLICATION_JSON));
     }
+
+    #[test]
+    fn test_compat_basic_auth() {
+        let req = &Request::from_client()
+            .with_path("/basic-auth/foo/bar")
+            .with_header(compat::COMPAT_HEADER, "1")
+            .with_header("authorization", "Basic Zm9vOmJhcg==");
+        let resp = basic_auth(req, &Params::from([("user", "foo"), ("passwd", "bar")])).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::OK);
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v, json!({"authenticated": true, "user": "foo"}));
+
+        let req = &Request::from_client()
+            .with_path("/basic-auth/foo/bar")
+            .with_header(compat::COMPAT_HEADER, "1")
+            .with_header("authorization", "Basic Zm9vOmZvbw==");
+        let mut resp = basic_auth(req, &Params::from([("user", "foo"), ("passwd", "bar")])).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(resp.get_header_str("www-authenticate"), Some("Basic realm=\"Fake Realm\""));
+        assert!(resp.take_body_bytes().is_empty());
+
+        let req = &Request::from_client()
+            .with_path("/basic-auth/foo/bar")
+            .with_header(compat::COMPAT_HEADER, "1")
+            .with_header("authorization", fastly::http::HeaderValue::from_bytes(b"Basic \xff").unwrap());
+        let resp = basic_auth(req, &Params::from([("user", "foo"), ("passwd", "bar")])).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_compat_bearer_failure() {
+        let req = &Request::from_client()
+            .with_path("/bearer")
+            .with_query_str("_httpbin=1");
+        let resp = bearer(req, &Params::default()).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(resp.get_header_str("www-authenticate"), Some("Bearer"));
+    }
+
+    #[test]
+    fn test_client_cert() {
+        let v = client_cert_to_value(Some(CLIENT_PEM), Some(ClientCertVerifyResult::Ok)).unwrap();
+        assert_eq!(v["presented"], true);
+        assert_eq!(v["verification"], "ok");
+        assert_eq!(v["certificate"]["subject"]["attributes"]["CN"], "client.example.com");
+    }
+
+    #[test]
+    fn test_client_cert_missing() {
+        let resp = client_cert(&Request::get("http://example.com/client-cert"), &Params::default());
+        assert!(matches!(resp, Err(Error::SslCertificateRequired(_))));
+        let v = client_cert_to_value(None, Some(ClientCertVerifyResult::Ok));
+        assert!(matches!(v, Err(Error::SslCertificateRequired(_))));
+        let v = client_cert_to_value(Some(CLIENT_PEM), Some(ClientCertVerifyResult::CertificateMissing));
+        assert!(matches!(v, Err(Error::SslCertificateRequired(_))));
+    }
+
+    #[test]
+    fn test_client_cert_rejected() {
+        let v = client_cert_to_value(Some(CLIENT_PEM), Some(ClientCertVerifyResult::CertificateExpired));
+        assert_eq!(v, Err(Error::SslCertificate(String::from("The client certificate was rejected: certificate_expired"))));
+        let v = client_cert_to_value(Some("-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----"), None);
+        assert!(matches!(v, Err(Error::SslCertificate(_))));
+    }
 }
@@ -0,0 +1,349 @@
+use base64::{Engine as _, engine::general_purpose};
+use crate::utils::{multi_map, sha256_hex};
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use std::net::IpAddr;
+
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const BMP_STRING: u8 = 0x1e;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+/// `[0] EXPLICIT Version` and `[3] EXPLICIT Extensions` in TBSCertificate.
+const VERSION: u8 = 0xa0;
+const EXTENSIONS: u8 = 0xa3;
+/// GeneralName choices, context-specific and implicitly tagged.
+const RFC822_NAME: u8 = 0x81;
+const DNS_NAME: u8 = 0x82;
+const URI: u8 = 0x86;
+const IP_ADDRESS: u8 = 0x87;
+
+const SUBJECT_ALT_NAME: &str = "2.5.29.17";
+
+/// Short names of the usual distinguished name attributes, as OpenSSL prints them.
+const ATTRIBUTES: [(&str, &str); 10] = [
+    ("2.5.4.3", "CN"),
+    ("2.5.4.5", "serialNumber"),
+    ("2.5.4.6", "C"),
+    ("2.5.4.7", "L"),
+    ("2.5.4.8", "ST"),
+    ("2.5.4.9", "street"),
+    ("2.5.4.10", "O"),
+    ("2.5.4.11", "OU"),
+    ("0.9.2342.19200300.100.1.25", "DC"),
+    ("1.2.840.113549.1.9.1", "emailAddress"),
+];
+
+/// Reads DER type-length-value elements.
+struct Der<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    fn peek_tag(&self) -> Option<u8> {
+        self.bytes.first().copied()
+    }
+
+    fn next(&mut self) -> Result<(u8, &'a [u8]), String> {
+        let truncated = || String::from("Truncated DER element");
+        let (&tag, rest) = self.bytes.split_first().ok_or_else(truncated)?;
+        let (&first, rest) = rest.split_first().ok_or_else(truncated)?;
+        let (len, rest) = match first {
+            len if len < 0x80 => (len as usize, rest),
+            0x81..=0x84 => {
+                let n = (first & 0x7f) as usize;
+                let len_bytes = rest.get(..n).ok_or_else(truncated)?;
+                (len_bytes.iter().fold(0, |len, b| (len << 8) | *b as usize), &rest[n..])
+            },
+            _ => return Err(format!("Unsupported DER length {:#x}", first)),
+        };
+        let value = rest.get(..len).ok_or_else(truncated)?;
+        self.bytes = &rest[len..];
+        Ok((tag, value))
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<&'a [u8], String> {
+        match self.next()? {
+            (tag, value) if tag == expected => Ok(value),
+            (tag, _) => Err(format!("Expected DER tag {:#04x}, found {:#04x}", expected, tag)),
+        }
+    }
+
+    fn expect_der(&mut self, expected: u8) -> Result<Der<'a>, String> {
+        self.expect(expected).map(|bytes| Der { bytes })
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn oid_to_string(bytes: &[u8]) -> String {
+    let mut arcs: Vec<u64> = vec![];
+    let mut arc: u64 = 0;
+    for b in bytes {
+        arc = (arc << 7) | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+    let first = arcs.first().copied().unwrap_or_default();
+    let (x, y) = if first < 80 { (first / 40, first % 40) } else { (2, first - 80) };
+    [x, y].iter().copied().chain(arcs.into_iter().skip(1))
+        .map(|arc| arc.to_string())
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+fn string_value(tag: u8, bytes: &[u8]) -> String {
+    match tag {
+        BMP_STRING => String::from_utf16_lossy(&bytes.chunks(2)
+            .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+            .collect::<Vec<u16>>()),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Escapes the characters RFC 4514 reserves in attribute values.
+fn escape_dn_value(value: &str) -> String {
+    value.chars().fold(String::new(), |mut escaped, c| {
+        if matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+/// A Name as its one-line form, `C=US, O=Example Corp, CN=example.com`, in
+/// certificate order as OpenSSL prints it, and as its attributes.
+fn name_to_value(mut name: Der) -> Result<Value, String> {
+    let mut attributes: Vec<(String, String)> = vec![];
+    while name.peek_tag().is_some() {
+        let mut rdn = name.expect_der(SET)?;
+        while rdn.peek_tag().is_some() {
+            let mut attribute = rdn.expect_der(SEQUENCE)?;
+            let oid = oid_to_string(attribute.expect(OID)?);
+            let (tag, value) = attribute.next()?;
+            let short_name = ATTRIBUTES.iter().find(|(o, _)| *o == oid).map(|(_, n)| n.to_string());
+            attributes.push((short_name.unwrap_or(oid), string_value(tag, value)));
+        }
+    }
+    let dn = attributes.iter()
+        .map(|(k, v)| format!("{}={}", k, escape_dn_value(v)))
+        .collect::<Vec<String>>()
+        .join(", ");
+    Ok(json!({
+        "dn": dn,
+        "attributes": multi_map(attributes.into_iter().map(|(k, v)| (k, json!(v)))),
+    }))
+}
+
+/// A UTCTime or GeneralizedTime in RFC 3339. Years 50 to 99 of a UTCTime are
+/// in the 1900s, as RFC 5280 has it.
+fn time_to_string(tag: u8, bytes: &[u8]) -> Result<String, String> {
+    let time = std::str::from_utf8(bytes).unwrap_or_default();
+    let digits = time.strip_suffix('Z').filter(|t| t.chars().all(|c| c.is_ascii_digit()));
+    let full = match (tag, digits) {
+        (UTC_TIME, Some(t)) if t.len() == 12 => format!("{}{}", if &t[..2] < "50" { "20" } else { "19" }, t),
+        (GENERALIZED_TIME, Some(t)) if t.len() == 14 => t.to_string(),
+        _ => return Err(format!("Unsupported time {:?}", time)),
+    };
+    Ok(format!("{}-{}-{}T{}:{}:{}Z", &full[..4], &full[4..6], &full[6..8], &full[8..10], &full[10..12], &full[12..14]))
+}
+
+fn subject_alt_names(mut names: Der) -> Result<Value, String> {
+    let (mut dns, mut ip, mut email, mut uri) = (vec![], vec![], vec![], vec![]);
+    while names.peek_tag().is_some() {
+        match names.next()? {
+            (DNS_NAME, name) => dns.push(string_value(DNS_NAME, name)),
+            (RFC822_NAME, name) => email.push(string_value(RFC822_NAME, name)),
+            (URI, name) => uri.push(string_value(URI, name)),
+            (IP_ADDRESS, name) => ip.push(<[u8; 4]>::try_from(name).map(IpAddr::from)
+                .or_else(|_| <[u8; 16]>::try_from(name).map(IpAddr::from))
+                .map(|ip| ip.to_string())
+                .unwrap_or_else(|_| hex(name))),
+            // otherName, x400Address, directoryName, ediPartyName and registeredID
+            _ => {},
+        }
+    }
+    Ok(json!({"dns": dns, "ip": ip, "email": email, "uri": uri}))
+}
+
+/// Decodes the first certificate of a PEM bundle into DER.
+pub fn from_pem(pem: &str) -> Result<Vec<u8>, String> {
+    let body: String = pem.lines()
+        .map(str::trim)
+        .skip_while(|line| *line != "-----BEGIN CERTIFICATE-----")
+        .skip(1)
+        .take_while(|line| *line != "-----END CERTIFICATE-----")
+        .collect();
+    if body.is_empty() {
+        return Err(String::from("No PEM certificate found"));
+    }
+    general_purpose::STANDARD.decode(body).map_err(|e| format!("Invalid PEM certificate: {}", e))
+}
+
+/// Describes an X.509 certificate: subject, issuer, subject alternative
+/// names, validity window, serial number and SHA-256 fingerprint. Only what
+/// an echo needs is parsed, and the signature is not checked.
+pub fn describe(der: &[u8]) -> Result<Value, String> {
+    let mut certificate = Der { bytes: der }.expect_der(SEQUENCE)?;
+    let mut tbs = certificate.expect_der(SEQUENCE)?;
+
+    let version = match tbs.peek_tag() {
+        Some(VERSION) => u32::from(tbs.expect_der(VERSION)?.expect(INTEGER)?.last().copied().unwrap_or_default()) + 1,
+        _ => 1,
+    };
+    let serial = tbs.expect(INTEGER)?;
+    // A positive serial with its high bit set is prefixed with a zero byte
+    let serial = match serial {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        serial => serial,
+    };
+    tbs.expect(SEQUENCE)?; // signature algorithm
+    let issuer = name_to_value(tbs.expect_der(SEQUENCE)?)?;
+    let mut validity = tbs.expect_der(SEQUENCE)?;
+    let (tag, not_before) = validity.next()?;
+    let not_before = time_to_string(tag, not_before)?;
+    let (tag, not_after) = validity.next()?;
+    let not_after = time_to_string(tag, not_after)?;
+    let subject = name_to_value(tbs.expect_der(SEQUENCE)?)?;
+    tbs.expect(SEQUENCE)?; // subject public key info
+
+    let mut san = json!({"dns": [], "ip": [], "email": [], "uri": []});
+    while let Some(tag) = tbs.peek_tag() {
+        if tag != EXTENSIONS {
+            tbs.next()?; // issuer and subject unique identifiers
+            continue;
+        }
+        let mut extensions = tbs.expect_der(EXTENSIONS)?.expect_der(SEQUENCE)?;
+        while extensions.peek_tag().is_some() {
+            let mut extension = extensions.expect_der(SEQUENCE)?;
+            let oid = oid_to_string(extension.expect(OID)?);
+            if extension.peek_tag() == Some(BOOLEAN) {
+                extension.next()?; // critical
+            }
+            let value = extension.expect(OCTET_STRING)?;
+            if oid == SUBJECT_ALT_NAME {
+                san = subject_alt_names(Der { bytes: value }.expect_der(SEQUENCE)?)?;
+            }
+        }
+    }
+
+    Ok(json!({
+        "version": version,
+        "serial": hex(serial),
+        "subject": subject,
+        "issuer": issuer,
+        "san": san,
+        "validity": {
+            "not_before": not_before,
+            "not_after": not_after,
+        },
+        "fingerprint_sha256": sha256_hex(der),
+    }))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// Issued by CA below, with DNS, IP, email and URI subject alternative names.
+    pub const CLIENT_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIICVDCCAfqgAwIBAgIDGis8MAoGCCqGSM49BAMCMEAxCzAJBgNVBAYTAlVTMRUw
+EwYDVQQKDAxFeGFtcGxlIENvcnAxGjAYBgNVBAMMEUV4YW1wbGUgQ2xpZW50IENB
+MCAXDTI2MTAxNzAyNDQyMloYDzIwNTQwMzA0MDI0NDIyWjBUMQswCQYDVQQGEwJV
+UzEVMBMGA1UECgwMRXhhbXBsZSBDb3JwMREwDwYDVQQLDAhQbGF0Zm9ybTEbMBkG
+A1UEAwwSY2xpZW50LmV4YW1wbGUuY29tMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcD
+QgAEaQo3PoxySgoBUI+YVWHUG5fANeZTQta7MSe2TXvrtEhaRHTqf1wZYSbJt2wj
+0rVfSLO1r/JemD0009z/V1kGKqOBzDCByTByBgNVHREEazBpghJjbGllbnQuZXhh
+bXBsZS5jb22CDSouZXhhbXBsZS5jb22HBMAAAgGHECABDbgAAAAAAAAAAAAAAAGB
+D29wc0BleGFtcGxlLmNvbYYbc3BpZmZlOi8vZXhhbXBsZS5jb20vY2xpZW50MBMG
+A1UdJQQMMAoGCCsGAQUFBwMCMB0GA1UdDgQWBBRwD48XSfbSib+t7gkcw6CfirSj
+vTAfBgNVHSMEGDAWgBR3D4BcsKdaGKMAVs2mj6FoCuOEkDAKBggqhkjOPQQDAgNI
+ADBFAiEAyhOM4eFmfFY+XqPPIgGD0sch3sIz790cBVduxtFNLW8CIEuVHzBpGgua
+9Piq3TbI5R/M85Q+kO/Eecpn4Lj+3OYk
+-----END CERTIFICATE-----
+";
+
+    /// Self-signed, without subject alternative names.
+    const CA_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIB1DCCAXugAwIBAgIUFsYhAwpgW5W2M3A7NF4SgxnP6vEwCgYIKoZIzj0EAwIw
+QDELMAkGA1UEBhMCVVMxFTATBgNVBAoMDEV4YW1wbGUgQ29ycDEaMBgGA1UEAwwR
+RXhhbXBsZSBDbGllbnQgQ0EwHhcNMjYxMDE3MDI0NDIyWhcNMzYxMDE0MDI0NDIy
+WjBAMQswCQYDVQQGEwJVUzEVMBMGA1UECgwMRXhhbXBsZSBDb3JwMRowGAYDVQQD
+DBFFeGFtcGxlIENsaWVudCBDQTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABIJi
+tCqQx5loOmOB465wdMiXhrbB0fLO1F5R6xAQPPf34lpAhQgc0C15N3cjB1a4rpN/
+srqwoPjGSqhX9i87Q4qjUzBRMB0GA1UdDgQWBBR3D4BcsKdaGKMAVs2mj6FoCuOE
+kDAfBgNVHSMEGDAWgBR3D4BcsKdaGKMAVs2mj6FoCuOEkDAPBgNVHRMBAf8EBTAD
+AQH/MAoGCCqGSM49BAMCA0cAMEQCIF9yKvtV+sZY/Nf2MG80UIxNNFlouSyJK7j8
+jC93wAWjAiBXwHdGKNQ4naDAwrWwLrM22Xv+bCm2EuQoAZvqTDObwA==
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn test_oid_to_string() {
+        assert_eq!(oid_to_string(&[0x55, 0x1d, 0x11]), "2.5.29.17");
+        assert_eq!(oid_to_string(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01]), "1.2.840.113549.1.9.1");
+    }
+
+    #[test]
+    fn test_time_to_string() {
+        assert_eq!(time_to_string(UTC_TIME, b"261017024422Z").unwrap(), "2026-10-17T02:44:22Z");
+        assert_eq!(time_to_string(UTC_TIME, b"991231235959Z").unwrap(), "1999-12-31T23:59:59Z");
+        assert_eq!(time_to_string(GENERALIZED_TIME, b"20540304024422Z").unwrap(), "2054-03-04T02:44:22Z");
+        assert!(time_to_string(UTC_TIME, b"2610170244Z").is_err());
+    }
+
+    #[test]
+    fn test_escape_dn_value() {
+        assert_eq!(escape_dn_value("Example, Inc."), "Example\\, Inc.");
+    }
+
+    #[test]
+    fn test_describe() {
+        let v = describe(&from_pem(CLIENT_PEM).unwrap()).unwrap();
+        assert_eq!(v["version"], 3);
+        assert_eq!(v["serial"], "1a2b3c");
+        assert_eq!(v["subject"]["dn"], "C=US, O=Example Corp, OU=Platform, CN=client.example.com");
+        assert_eq!(v["subject"]["attributes"]["CN"], "client.example.com");
+        assert_eq!(v["issuer"]["dn"], "C=US, O=Example Corp, CN=Example Client CA");
+        assert_eq!(v["san"], json!({
+            "dns": ["client.example.com", "*.example.com"],
+            "ip": ["192.0.2.1", "2001:db8::1"],
+            "email": ["ops@example.com"],
+            "uri": ["spiffe://example.com/client"],
+        }));
+        assert_eq!(v["validity"], json!({"not_before": "2026-10-17T02:44:22Z", "not_after": "2054-03-04T02:44:22Z"}));
+        assert_eq!(v["fingerprint_sha256"], "769ec8e9dc26bac9354bfc47ccc794d81ce4998099d6bb4e2adce6325a14388a");
+    }
+
+    #[test]
+    fn test_describe_self_signed() {
+        let v = describe(&from_pem(CA_PEM).unwrap()).unwrap();
+        assert_eq!(v["serial"], "16c621030a605b95b633703b345e128319cfeaf1");
+        assert_eq!(v["subject"], v["issuer"]);
+        assert_eq!(v["san"]["dns"], json!([]));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(from_pem("not a certificate").is_err());
+        let der = from_pem(CLIENT_PEM).unwrap();
+        assert!(describe(&der[..100]).is_err());
+    }
+
+    #[test]
+    fn test_describe_unknown_version() {
+        let mut der = from_pem(CLIENT_PEM).unwrap();
+        let at = der.windows(5).position(|w| w == [VERSION, 3, INTEGER, 1, 2]).unwrap();
+        der[at + 4] = 0xff;
+        assert_eq!(describe(&der).unwrap()["version"], 256);
+    }
+}
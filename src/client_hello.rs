@@ -0,0 +1,143 @@
+/// The parts of a TLS ClientHello that Fastly does not expose on its own.
+#[derive(Debug, Default, PartialEq)]
+pub struct ClientHello {
+    /// The host name from the server_name extension.
+    pub server_name: Option<String>,
+    /// The protocols offered in the application_layer_protocol_negotiation
+    /// extension, in the client's order of preference.
+    pub alpn: Vec<String>,
+}
+
+const SERVER_NAME: u16 = 0;
+const ALPN: u16 = 16;
+const HOST_NAME: u8 = 0;
+
+/// Reads the ClientHello fields, without trusting any length it declares.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < n {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    /// A vector prefixed with its length on `len_bytes` bytes.
+    fn vec(&mut self, len_bytes: usize) -> Option<Reader<'a>> {
+        let len = match len_bytes {
+            1 => self.u8()? as usize,
+            _ => self.u16()? as usize,
+        };
+        self.take(len).map(|bytes| Reader { bytes })
+    }
+}
+
+/// Parses a ClientHello, with or without its record and handshake headers.
+/// None if it is malformed.
+pub fn parse(bytes: &[u8]) -> Option<ClientHello> {
+    let mut r = Reader { bytes };
+    // Record header: content type 22 (handshake), version, length
+    if r.bytes.first() == Some(&22) {
+        r.take(5)?;
+    }
+    // Handshake header: type 1 (client_hello), 24 bit length
+    if r.bytes.first() == Some(&1) {
+        r.take(4)?;
+    }
+    r.take(2 + 32)?; // legacy_version, random
+    r.vec(1)?; // legacy_session_id
+    r.vec(2)?; // cipher_suites
+    r.vec(1)?; // legacy_compression_methods
+
+    let mut hello = ClientHello::default();
+    // A ClientHello without extensions is valid
+    if r.bytes.is_empty() {
+        return Some(hello);
+    }
+    let mut extensions = r.vec(2)?;
+    while !extensions.bytes.is_empty() {
+        let kind = extensions.u16()?;
+        let mut data = extensions.vec(2)?;
+        match kind {
+            SERVER_NAME => {
+                let mut names = data.vec(2)?;
+                while !names.bytes.is_empty() {
+                    let name_type = names.u8()?;
+                    let name = names.vec(2)?;
+                    if name_type == HOST_NAME {
+                        hello.server_name = Some(String::from_utf8_lossy(name.bytes).into_owned());
+                    }
+                }
+            },
+            ALPN => {
+                let mut protocols = data.vec(2)?;
+                while !protocols.bytes.is_empty() {
+                    hello.alpn.push(String::from_utf8_lossy(protocols.vec(1)?.bytes).into_owned());
+                }
+            },
+            _ => {},
+        }
+    }
+    Some(hello)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn with_len16(bytes: &[u8]) -> Vec<u8> {
+        [&(bytes.len() as u16).to_be_bytes()[..], bytes].concat()
+    }
+
+    /// Builds a ClientHello handshake message with the given extensions.
+    fn client_hello(server_name: &str, alpn: &[&str]) -> Vec<u8> {
+        let sni = with_len16(&[&[HOST_NAME][..], &with_len16(server_name.as_bytes())].concat());
+        let protocols: Vec<u8> = alpn.iter().flat_map(|p| [&[p.len() as u8][..], p.as_bytes()].concat()).collect();
+        let extensions = [
+            &SERVER_NAME.to_be_bytes()[..], &with_len16(&sni),
+            &ALPN.to_be_bytes()[..], &with_len16(&with_len16(&protocols)),
+        ].concat();
+        let body = [
+            &[3, 3][..], &[0; 32], // legacy_version, random
+            &[0], // legacy_session_id
+            &with_len16(&[0x13, 0x01]), // TLS_AES_128_GCM_SHA256
+            &[1, 0], // null compression
+            &with_len16(&extensions),
+        ].concat();
+        let len = (body.len() as u32).to_be_bytes();
+        [&[1][..], &len[1..], &body].concat()
+    }
+
+    #[test]
+    fn test_parse() {
+        let hello = client_hello("example.com", &["h2", "http/1.1"]);
+        let expected = ClientHello {
+            server_name: Some(String::from("example.com")),
+            alpn: vec![String::from("h2"), String::from("http/1.1")],
+        };
+        assert_eq!(parse(&hello), Some(expected));
+
+        let record = [&[22, 3, 1][..], &with_len16(&hello)].concat();
+        assert_eq!(parse(&record).unwrap().server_name, Some(String::from("example.com")));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let hello = client_hello("example.com", &["h2"]);
+        assert_eq!(parse(&hello[..hello.len() - 3]), None);
+        assert_eq!(parse(b""), None);
+    }
+}
@@ -0,0 +1,165 @@
+use fastly::Request;
+use crate::config::CONFIG;
+use serde_json::{json, Map, Value};
+
+/// Request header turning on httpbin compatibility for one request.
+pub const COMPAT_HEADER: &str = "x-httpbin-compat";
+/// Query parameter turning on httpbin compatibility for one request.
+pub const COMPAT_PARAM: &str = "_httpbin";
+
+fn is_true(value: &str) -> bool {
+    matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on")
+}
+
+/// Whether responses should reproduce httpbin's output shapes, either for
+/// every request through the config store or for this request only.
+pub fn enabled(req: &Request) -> bool {
+    CONFIG.httpbin_compat
+        || req.get_header(COMPAT_HEADER).and_then(|v| v.to_str().ok()).is_some_and(is_true)
+        || req.get_query_parameter(COMPAT_PARAM).is_some_and(is_true)
+}
+
+/// `x-forwarded-for` becomes `X-Forwarded-For`.
+fn title_case(name: &str) -> String {
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + &chars.as_str().to_ascii_lowercase(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+/// The request headers as httpbin shows them: Title-Cased, repeated headers
+/// joined with commas, without the header turning compatibility on.
+pub fn headers(req: &Request) -> Map<String, Value> {
+    let mut headers: Map<String, Value> = Map::new();
+    for (name, value) in req.get_headers() {
+        if name == COMPAT_HEADER {
+            continue;
+        }
+        let value = value.to_str().unwrap_or_default();
+        headers.entry(title_case(name.as_str()))
+            .and_modify(|v| *v = json!(format!("{},{}", v.as_str().unwrap_or_default(), value)))
+            .or_insert_with(|| json!(value));
+    }
+    headers
+}
+
+/// The X-Forwarded-For chain followed by the client address, comma-joined
+/// as httpbin reports `origin`.
+pub fn origin(req: &Request) -> String {
+    req.get_header_all("x-forwarded-for")
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+        .map(String::from)
+        .chain(req.get_client_ip_addr().map(|ip| ip.to_string()))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Reshapes an echo into httpbin's: fields httpbin does not have are dropped,
+/// headers and origin follow httpbin's conventions and files only hold
+/// their content.
+pub fn reshape(req: &Request, mut value: Value) -> Value {
+    let Value::Object(fields) = &mut value else {
+        return value;
+    };
+
+    for field in ["request_id", "decoded_from", "json_error", "proxy_chain"] {
+        fields.remove(field);
+    }
+    if let Some(Value::Object(args)) = fields.get_mut("args") {
+        args.remove(COMPAT_PARAM);
+    }
+    if fields.contains_key("headers") {
+        fields.insert(String::from("headers"), Value::Object(headers(req)));
+    }
+    if fields.contains_key("origin") {
+        fields.insert(String::from("origin"), json!(origin(req)));
+    }
+    if let Some(Value::Object(files)) = fields.get_mut("files") {
+        for file in files.values_mut() {
+            *file = match file.take() {
+                Value::Array(files) => Value::Array(files.into_iter().map(|f| f["content"].clone()).collect()),
+                f => f["content"].clone(),
+            };
+        }
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastly::http::HeaderValue;
+
+    #[test]
+    fn test_title_case() {
+        assert_eq!(title_case("x-forwarded-for"), "X-Forwarded-For");
+        assert_eq!(title_case("USER-AGENT"), "User-Agent");
+        assert_eq!(title_case("dnt"), "Dnt");
+    }
+
+    #[test]
+    fn test_enabled() {
+        assert!(!enabled(&Request::from_client()));
+        assert!(enabled(&Request::from_client().with_header(COMPAT_HEADER, "true")));
+        assert!(enabled(&Request::from_client().with_query_str("_httpbin=1")));
+        assert!(!enabled(&Request::from_client().with_query_str("_httpbin=0")));
+        assert!(!enabled(&Request::from_client().with_header(COMPAT_HEADER, HeaderValue::from_bytes(b"\xff").unwrap())));
+    }
+
+    #[test]
+    fn test_headers() {
+        let mut req = Request::from_client()
+            .with_header("accept", "text/html")
+            .with_header("x-request-id", "foo")
+            .with_header(COMPAT_HEADER, "1");
+        req.append_header("accept", "application/json");
+        assert_eq!(Value::Object(headers(&req)), json!({"Accept": "text/html,application/json", "X-Request-Id": "foo"}));
+    }
+
+    #[test]
+    fn test_origin() {
+        let req = Request::from_client()
+            .with_header("x-forwarded-for", "203.0.113.1, 198.51.100.2");
+        assert!(origin(&req).starts_with("203.0.113.1, 198.51.100.2"));
+
+        let mut req = Request::from_client()
+            .with_header("x-forwarded-for", HeaderValue::from_bytes(b"\xff").unwrap());
+        req.append_header("x-forwarded-for", "203.0.113.1");
+        assert!(origin(&req).starts_with("203.0.113.1"));
+    }
+
+    #[test]
+    fn test_reshape() {
+        let req = Request::from_client()
+            .with_header("user-agent", "curl");
+        let value = json!({
+            "args": {"_httpbin": "1", "a": "b"},
+            "data": "",
+            "decoded_from": "gzip",
+            "files": {"f": {"filename": "a.txt", "content": "abc"}},
+            "form": {},
+            "headers": {"user-agent": "curl"},
+            "json": null,
+            "proxy_chain": {},
+            "request_id": "foo",
+        });
+        assert_eq!(reshape(&req, value), json!({
+            "args": {"a": "b"},
+            "data": "",
+            "files": {"f": "abc"},
+            "form": {},
+            "headers": {"User-Agent": "curl"},
+            "json": null,
+        }));
+    }
+}
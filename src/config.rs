@@ -0,0 +1,116 @@
+use fastly::ConfigStore;
+use crate::cors::CorsPolicy;
+use crate::limits::Limits;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+/// Name of the Fastly config store RestReflect reads its settings from.
+const CONFIG_STORE: &str = "restreflect";
+
+pub static CONFIG: LazyLock<Config> = LazyLock::new(Config::load);
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    /// Prefix RestReflect is mounted under, e.g. `/echo`. Empty when mounted at the root.
+    pub base_path: String,
+    /// Reproduce httpbin's output shapes for every request.
+    pub httpbin_compat: bool,
+    pub limits: Limits,
+    pub cors: CorsPolicy,
+}
+
+/// Turns `echo`, `/echo/` or `/echo` into `/echo`, and `/` into an empty prefix.
+fn normalize_base_path(base_path: &str) -> String {
+    let base_path = base_path.trim().trim_matches('/');
+    if base_path.is_empty() {
+        return String::new();
+    }
+    format!("/{}", base_path)
+}
+
+/// Parses a setting, falling back to the default when it is missing or invalid.
+fn parse_or<T: FromStr>(value: Option<String>, default: T) -> T {
+    value.and_then(|v| v.trim().parse().ok()).unwrap_or(default)
+}
+
+/// Splits a comma-separated setting, dropping empty items.
+fn parse_list(value: &str) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|v| !v.is_empty()).map(String::from).collect()
+}
+
+impl Config {
+    /// Reads the settings from the config store, using defaults when the store
+    /// or a key is missing.
+    fn load() -> Self {
+        let store = ConfigStore::try_open(CONFIG_STORE).ok();
+        let get = |key: &str| store.as_ref().and_then(|s| s.try_get(key).ok().flatten());
+
+        let limits = Limits::default();
+        let cors = CorsPolicy::default();
+        Config {
+            base_path: normalize_base_path(&get("base_path").unwrap_or_default()),
+            httpbin_compat: parse_or(get("httpbin_compat"), false),
+            limits: Limits {
+                max_body_bytes: parse_or(get("max_body_bytes"), limits.max_body_bytes),
+                max_url_bytes: parse_or(get("max_url_bytes"), limits.max_url_bytes),
+                max_header_count: parse_or(get("max_header_count"), limits.max_header_count),
+                max_header_bytes: parse_or(get("max_header_bytes"), limits.max_header_bytes),
+            },
+            cors: CorsPolicy {
+                allow_origin: get("cors_allow_origin").map(|v| v.trim().to_string()).filter(|v| !v.is_empty()),
+                allow_credentials: parse_or(get("cors_allow_credentials"), cors.allow_credentials),
+                expose_headers: get("cors_expose_headers").map(|v| parse_list(&v)).unwrap_or(cors.expose_headers),
+                max_age: get("cors_max_age").and_then(|v| v.trim().parse().ok()).or(cors.max_age),
+            },
+        }
+    }
+
+    /// Returns the path relative to the base path, or None if the path is
+    /// outside of it.
+    pub fn strip_base_path<'a>(&self, path: &'a str) -> Option<&'a str> {
+        match path.strip_prefix(self.base_path.as_str()) {
+            Some("") => Some("/"),
+            Some(rest) if rest.starts_with('/') => Some(rest),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_base_path() {
+        assert_eq!(normalize_base_path(""), "");
+        assert_eq!(normalize_base_path("/"), "");
+        assert_eq!(normalize_base_path("echo"), "/echo");
+        assert_eq!(normalize_base_path("/echo/"), "/echo");
+        assert_eq!(normalize_base_path("/echo/v1"), "/echo/v1");
+    }
+
+    #[test]
+    fn test_parse_or() {
+        assert_eq!(parse_or(Some(String::from(" 42 ")), 7), 42);
+        assert_eq!(parse_or(Some(String::from("lots")), 7), 7);
+        assert_eq!(parse_or(None, 7), 7);
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(parse_list("etag, x-foo,,"), vec!["etag", "x-foo"]);
+        assert!(parse_list(" ").is_empty());
+    }
+
+    #[test]
+    fn test_strip_base_path() {
+        let config = Config { base_path: String::from("/echo"), ..Config::default() };
+        assert_eq!(config.strip_base_path("/echo/get"), Some("/get"));
+        assert_eq!(config.strip_base_path("/echo"), Some("/"));
+        assert_eq!(config.strip_base_path("/echoes/get"), None);
+        assert_eq!(config.strip_base_path("/get"), None);
+
+        let config = Config::default();
+        assert_eq!(config.strip_base_path("/get"), Some("/get"));
+    }
+}
@@ -1,6 +1,9 @@
 use fastly::http::StatusCode;
-use fastly::{Error, mime, Request, Response};
+use fastly::{mime, Request, Response};
+use crate::config::CONFIG;
+use crate::error::Error;
 use serde_json::{json, to_string_pretty};
+use crate::router::Params;
 
 #[utoipa::path(
     get,
@@ -11,7 +14,7 @@ use serde_json::{json, to_string_pretty};
     )
 )]
 /// Returns cookie data.
-pub fn get_cookies(req: &Request) -> Result<Response, Error> {
+pub fn get_cookies(req: &Request, _: &Params) -> Result<Response, Error> {
     let cookies: Vec<(String, String)> = req.get_header_str("cookie")
         .map(|cookie_str| {
             cookie_str.split(';')
@@ -39,6 +42,14 @@ pub fn get_cookies(req: &Request) -> Result<Response, Error> {
         .with_body(to_string_pretty(&resp).unwrap_or_default()))
 }
 
+/// The Path attribute scoping cookies to where RestReflect is mounted.
+fn cookie_path(base_path: &str) -> &str {
+    match base_path {
+        "" => "/",
+        base_path => base_path,
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/cookies/set/{name}/{value}",
@@ -52,25 +63,16 @@ pub fn get_cookies(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Sets a cookie.
-pub fn set_cookie(req: &Request) -> Result<Response, Error> {
-    use regex_lite::Regex;
+pub fn set_cookie(_: &Request, params: &Params) -> Result<Response, Error> {
+    let (name, value) = match (params.get("name"), params.get("value")) {
+        (Some(name), Some(value)) => (name, value),
+        _ => return Err(Error::BadRequest(String::from("Invalid cookie parameters"))),
+    };
 
-    let caps = Regex::new(r"/cookies/set/([^/]+)/([^/]+)$")?
-        .captures(req.get_path());
-    
-    if let Some(caps) = caps {
-        let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-        let value = caps.get(2).map(|m| m.as_str()).unwrap_or("");
-        
-        return Ok(Response::from_status(StatusCode::OK)
-            .with_content_type(mime::APPLICATION_JSON)
-            .with_header("Set-Cookie", format!("{}={}; Path=/", name, value))
-            .with_body(to_string_pretty(&json!({"success": true})).unwrap_or_default()));
-    }
-
-    Ok(Response::from_status(StatusCode::BAD_REQUEST)
-        .with_content_type(mime::TEXT_PLAIN)
-        .with_body("Invalid cookie parameters"))
+    Ok(Response::from_status(StatusCode::OK)
+        .with_content_type(mime::APPLICATION_JSON)
+        .with_header("Set-Cookie", format!("{}={}; Path={}", name, value, cookie_path(&CONFIG.base_path)))
+        .with_body(to_string_pretty(&json!({"success": true})).unwrap_or_default()))
 }
 
 #[utoipa::path(
@@ -85,24 +87,14 @@ pub fn set_cookie(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Deletes a cookie.
-pub fn delete_cookie(req: &Request) -> Result<Response, Error> {
-    use regex_lite::Regex;
+pub fn delete_cookie(_: &Request, params: &Params) -> Result<Response, Error> {
+    let name = params.get("name")
+        .ok_or_else(|| Error::BadRequest(String::from("Invalid cookie name")))?;
 
-    let caps = Regex::new(r"/cookies/delete/([^/]+)$")?
-        .captures(req.get_path());
-    
-    if let Some(caps) = caps {
-        let name = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-        
-        return Ok(Response::from_status(StatusCode::OK)
-            .with_content_type(mime::APPLICATION_JSON)
-            .with_header("Set-Cookie", format!("{}=; Path=/; Expires=Thu, 01 Jan 1970 00:00:00 GMT", name))
-            .with_body(to_string_pretty(&json!({"success": true})).unwrap_or_default()));
-    }
-
-    Ok(Response::from_status(StatusCode::BAD_REQUEST)
-        .with_content_type(mime::TEXT_PLAIN)
-        .with_body("Invalid cookie name"))
+    Ok(Response::from_status(StatusCode::OK)
+        .with_content_type(mime::APPLICATION_JSON)
+        .with_header("Set-Cookie", format!("{}=; Path={}; Expires=Thu, 01 Jan 1970 00:00:00 GMT", name, cookie_path(&CONFIG.base_path)))
+        .with_body(to_string_pretty(&json!({"success": true})).unwrap_or_default()))
 }
 
 #[cfg(test)]
@@ -113,7 +105,7 @@ mod test {
     fn test_cookies() {
         let req = &Request::from_client()
             .with_header("cookie", "foo=bar; baz=qux");
-        let resp = get_cookies(req);
+        let resp = get_cookies(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -124,11 +116,17 @@ mod test {
         assert_eq!(json["cookies"]["baz"], "qux");
     }
 
+    #[test]
+    fn test_cookie_path() {
+        assert_eq!(cookie_path(""), "/");
+        assert_eq!(cookie_path("/echo"), "/echo");
+    }
+
     #[test]
     fn test_set_cookie() {
         let req = &Request::from_client()
             .with_path("/cookies/set/foo/bar");
-        let resp = set_cookie(req);
+        let resp = set_cookie(req, &Params::from([("name", "foo"), ("value", "bar")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -142,7 +140,7 @@ mod test {
     fn test_delete_cookie() {
         let req = &Request::from_client()
             .with_path("/cookies/delete/foo");
-        let resp = delete_cookie(req);
+        let resp = delete_cookie(req, &Params::from([("name", "foo")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -0,0 +1,325 @@
+use fastly::http::header::HeaderName;
+use fastly::http::{header, HeaderValue, Method, StatusCode};
+use fastly::{mime, Request, Response};
+use crate::config::CONFIG;
+use crate::error::Error;
+use crate::middleware::Middleware;
+use serde_json::{json, to_string_pretty};
+use crate::router::Params;
+use crate::timing::SERVER_TIMING;
+use crate::utils::X_REQUEST_ID;
+
+/// CORS policy applied to every response, except those from `/cors`. Each
+/// field is configurable from the config store, prefixed with `cors_`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorsPolicy {
+    /// Value of Access-Control-Allow-Origin. None reflects the request's Origin,
+    /// and so does `*` when credentials are allowed.
+    pub allow_origin: Option<String>,
+    pub allow_credentials: bool,
+    /// Value of Access-Control-Expose-Headers. Empty exposes every header
+    /// present on the response, along with X-Request-Id and Server-Timing,
+    /// which outer middlewares add later.
+    pub expose_headers: Vec<String>,
+    /// Value of Access-Control-Max-Age sent on preflight responses, in seconds.
+    pub max_age: Option<u32>,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        CorsPolicy {
+            allow_origin: None,
+            allow_credentials: true,
+            expose_headers: vec![],
+            max_age: Some(600),
+        }
+    }
+}
+
+/// Whether the request is a CORS preflight request.
+pub fn is_preflight(req: &Request) -> bool {
+    req.get_method() == Method::OPTIONS
+        && req.contains_header(header::ORIGIN)
+        && req.contains_header(header::ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+impl CorsPolicy {
+    /// Adds the CORS headers for the request to the response.
+    ///
+    /// Credentials are never combined with a wildcard origin, which browsers
+    /// reject: the request's Origin is returned instead, and without an Origin
+    /// header, `*` is returned without credentials.
+    pub fn apply(&self, req: &Request, mut resp: Response) -> Response {
+        let origin = match (self.allow_origin.as_deref(), req.get_header(header::ORIGIN).and_then(|v| v.to_str().ok())) {
+            (Some("*"), Some(origin)) if self.allow_credentials => origin.to_string(),
+            (Some(allow_origin), _) => allow_origin.to_string(),
+            (None, Some(origin)) => origin.to_string(),
+            (None, None) => {
+                resp.set_header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+                return resp;
+            },
+        };
+
+        if is_preflight(req) {
+            if let Some(method) = req.get_header(header::ACCESS_CONTROL_REQUEST_METHOD) {
+                resp.set_header(header::ACCESS_CONTROL_ALLOW_METHODS, method.clone());
+            }
+            if let Some(headers) = req.get_header(header::ACCESS_CONTROL_REQUEST_HEADERS) {
+                resp.set_header(header::ACCESS_CONTROL_ALLOW_HEADERS, headers.clone());
+            }
+            if let Some(max_age) = self.max_age {
+                resp.set_header(header::ACCESS_CONTROL_MAX_AGE, max_age.to_string());
+            }
+        } else {
+            let expose_headers = if self.expose_headers.is_empty() {
+                let mut names = resp.get_header_names_str().into_iter().map(String::from).collect::<Vec<String>>();
+                for name in [X_REQUEST_ID, SERVER_TIMING].iter() {
+                    if !names.iter().any(|n| n == name) {
+                        names.push(name.to_string());
+                    }
+                }
+                names
+            } else {
+                self.expose_headers.clone()
+            };
+            if !expose_headers.is_empty() {
+                resp.set_header(header::ACCESS_CONTROL_EXPOSE_HEADERS, expose_headers.join(", "));
+            }
+        }
+
+        if self.allow_credentials && origin != "*" {
+            resp.set_header(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+        }
+        resp.set_header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        resp.append_header(header::VARY, "Origin");
+        resp
+    }
+}
+
+impl Middleware for CorsPolicy {
+    fn name(&self) -> &'static str {
+        "cors"
+    }
+
+    fn after(&mut self, req: &Request, resp: Response) -> Response {
+        // /cors returns exactly the CORS headers it was asked for
+        if CONFIG.strip_base_path(req.get_path()) == Some("/cors") {
+            return resp;
+        }
+
+        // CORS headers already set, by response overrides, win over the policy
+        let set: Vec<(HeaderName, HeaderValue)> = resp.get_headers()
+            .filter(|(name, _)| name.as_str().starts_with("access-control-"))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        let mut resp = self.apply(req, resp);
+        for (name, _) in &set {
+            resp.remove_header(name);
+        }
+        for (name, value) in set {
+            resp.append_header(name, value);
+        }
+        resp
+    }
+}
+
+/// Query parameters accepted by /cors, and the header each one sets.
+const CORS_PARAMS: [(&str, header::HeaderName); 6] = [
+    ("allow_origin", header::ACCESS_CONTROL_ALLOW_ORIGIN),
+    ("allow_credentials", header::ACCESS_CONTROL_ALLOW_CREDENTIALS),
+    ("allow_methods", header::ACCESS_CONTROL_ALLOW_METHODS),
+    ("allow_headers", header::ACCESS_CONTROL_ALLOW_HEADERS),
+    ("expose_headers", header::ACCESS_CONTROL_EXPOSE_HEADERS),
+    ("max_age", header::ACCESS_CONTROL_MAX_AGE),
+];
+
+fn rr_cors(req: &Request) -> Result<Response, Error> {
+    let arg_pairs: Vec<(String, String)> = req.get_query().unwrap_or_default();
+    let status = if req.get_method() == Method::OPTIONS { StatusCode::NO_CONTENT } else { StatusCode::OK };
+    let mut resp = Response::from_status(status);
+    let mut set = serde_json::Map::new();
+
+    for (param, name) in CORS_PARAMS.iter() {
+        for (_, v) in arg_pairs.iter().filter(|(k, _)| k == param) {
+            let value = HeaderValue::from_str(v)
+                .map_err(|_| Error::BadRequest(format!("Invalid header: {}={}", param, v)))?;
+            resp.append_header(name, value);
+            set.insert(name.to_string(), json!(v));
+        }
+    }
+
+    if status == StatusCode::NO_CONTENT {
+        return Ok(resp);
+    }
+    Ok(resp
+        .with_content_type(mime::APPLICATION_JSON)
+        .with_body(to_string_pretty(&json!({"headers": set})).unwrap_or_default()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/cors",
+    tag = "CORS",
+    params(
+        ("allow_origin" = Option<String>, Query, description = "Access-Control-Allow-Origin to return"),
+        ("allow_credentials" = Option<String>, Query, description = "Access-Control-Allow-Credentials to return"),
+        ("allow_methods" = Option<String>, Query, description = "Access-Control-Allow-Methods to return"),
+        ("allow_headers" = Option<String>, Query, description = "Access-Control-Allow-Headers to return"),
+        ("expose_headers" = Option<String>, Query, description = "Access-Control-Expose-Headers to return"),
+        ("max_age" = Option<String>, Query, description = "Access-Control-Max-Age to return"),
+    ),
+    responses(
+        (status = 200, description = "The CORS headers that were set.", content_type = "application/json")
+    )
+)]
+/// Returns exactly the CORS headers given in the query string, and no others.
+pub fn get(req: &Request, _: &Params) -> Result<Response, Error> {
+    rr_cors(req)
+}
+
+#[utoipa::path(
+    post,
+    path = "/cors",
+    tag = "CORS",
+    params(
+        ("allow_origin" = Option<String>, Query, description = "Access-Control-Allow-Origin to return"),
+        ("allow_credentials" = Option<String>, Query, description = "Access-Control-Allow-Credentials to return"),
+        ("allow_methods" = Option<String>, Query, description = "Access-Control-Allow-Methods to return"),
+        ("allow_headers" = Option<String>, Query, description = "Access-Control-Allow-Headers to return"),
+        ("expose_headers" = Option<String>, Query, description = "Access-Control-Expose-Headers to return"),
+        ("max_age" = Option<String>, Query, description = "Access-Control-Max-Age to return"),
+    ),
+    responses(
+        (status = 200, description = "The CORS headers that were set.", content_type = "application/json")
+    )
+)]
+/// Returns exactly the CORS headers given in the query string, and no others.
+pub fn post(req: &Request, _: &Params) -> Result<Response, Error> {
+    rr_cors(req)
+}
+
+#[utoipa::path(
+    options,
+    path = "/cors",
+    tag = "CORS",
+    params(
+        ("allow_origin" = Option<String>, Query, description = "Access-Control-Allow-Origin to return"),
+        ("allow_credentials" = Option<String>, Query, description = "Access-Control-Allow-Credentials to return"),
+        ("allow_methods" = Option<String>, Query, description = "Access-Control-Allow-Methods to return"),
+        ("allow_headers" = Option<String>, Query, description = "Access-Control-Allow-Headers to return"),
+        ("expose_headers" = Option<String>, Query, description = "Access-Control-Expose-Headers to return"),
+        ("max_age" = Option<String>, Query, description = "Access-Control-Max-Age to return"),
+    ),
+    responses(
+        (status = 204, description = "A preflight response with the given CORS headers.")
+    )
+)]
+/// Answers a preflight request with exactly the CORS headers given in the query string.
+pub fn options(req: &Request, _: &Params) -> Result<Response, Error> {
+    rr_cors(req)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_no_origin() {
+        let req = &Request::from_client()
+            .with_path("/get");
+        let resp = CorsPolicy::default().apply(req, Response::from_status(StatusCode::OK));
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("*"));
+        assert_eq!(resp.get_header_str("access-control-allow-credentials"), None);
+    }
+
+    #[test]
+    fn test_reflect_origin() {
+        let req = &Request::from_client()
+            .with_header("origin", "https://example.org")
+            .with_path("/get");
+        let resp = Response::from_status(StatusCode::OK)
+            .with_header("etag", "foo");
+        let resp = CorsPolicy::default().apply(req, resp);
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("https://example.org"));
+        assert_eq!(resp.get_header_str("access-control-allow-credentials"), Some("true"));
+        assert_eq!(resp.get_header_str("access-control-expose-headers"), Some("etag, x-request-id, server-timing"));
+        assert_eq!(resp.get_header_str("vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn test_configured_origin() {
+        let req = &Request::from_client()
+            .with_header("origin", "https://example.org")
+            .with_path("/get");
+        let policy = CorsPolicy { allow_origin: Some(String::from("https://app.example.com")), ..CorsPolicy::default() };
+        let resp = policy.apply(req, Response::from_status(StatusCode::OK));
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("https://app.example.com"));
+        assert_eq!(resp.get_header_str("access-control-allow-credentials"), Some("true"));
+
+        let policy = CorsPolicy { allow_origin: Some(String::from("*")), allow_credentials: false, ..CorsPolicy::default() };
+        let resp = policy.apply(req, Response::from_status(StatusCode::OK));
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("*"));
+        assert_eq!(resp.get_header_str("access-control-allow-credentials"), None);
+    }
+
+    #[test]
+    fn test_wildcard_with_credentials() {
+        let policy = CorsPolicy { allow_origin: Some(String::from("*")), ..CorsPolicy::default() };
+        let req = &Request::from_client()
+            .with_header("origin", "https://example.org")
+            .with_path("/get");
+        let resp = policy.apply(req, Response::from_status(StatusCode::OK));
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("https://example.org"));
+        assert_eq!(resp.get_header_str("access-control-allow-credentials"), Some("true"));
+
+        let req = &Request::from_client()
+            .with_path("/get");
+        let resp = policy.apply(req, Response::from_status(StatusCode::OK));
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("*"));
+        assert_eq!(resp.get_header_str("access-control-allow-credentials"), None);
+    }
+
+    #[test]
+    fn test_preflight() {
+        let req = &Request::from_client()
+            .with_method(Method::OPTIONS)
+            .with_header("origin", "https://example.org")
+            .with_header("access-control-request-method", "PUT")
+            .with_header("access-control-request-headers", "x-foo, content-type")
+            .with_path("/put");
+        let resp = CorsPolicy::default().apply(req, Response::from_status(StatusCode::NO_CONTENT));
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("https://example.org"));
+        assert_eq!(resp.get_header_str("access-control-allow-methods"), Some("PUT"));
+        assert_eq!(resp.get_header_str("access-control-allow-headers"), Some("x-foo, content-type"));
+        assert_eq!(resp.get_header_str("access-control-max-age"), Some("600"));
+    }
+
+    #[test]
+    fn test_cors_endpoint() {
+        let req = &Request::from_client()
+            .with_path("/cors")
+            .with_query_str("allow_origin=*&allow_credentials=true");
+        let resp = get(req, &Params::default()).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::OK);
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("*"));
+        assert_eq!(resp.get_header_str("access-control-allow-credentials"), Some("true"));
+        assert_eq!(resp.get_header_str("access-control-max-age"), None);
+    }
+
+    #[test]
+    fn test_invalid_origin() {
+        let req = &Request::from_client()
+            .with_header("origin", HeaderValue::from_bytes(b"https://caf\xe9.example").unwrap())
+            .with_path("/get");
+        let resp = CorsPolicy::default().apply(req, Response::from_status(StatusCode::OK));
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("*"));
+    }
+
+    #[test]
+    fn test_cors_endpoint_invalid_value() {
+        let req = &Request::from_client()
+            .with_path("/cors")
+            .with_query_str("allow_origin=a%0Ab");
+        assert!(matches!(get(req, &Params::default()), Err(Error::BadRequest(_))));
+    }
+}
@@ -0,0 +1,123 @@
+use fastly::http::header;
+use fastly::Request;
+use crate::error::Error;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::io::Read;
+
+/// Reads at most one byte past `max_bytes`, so that a small compressed body
+/// cannot expand without bound.
+fn read_all(reader: impl Read, max_bytes: u64) -> Result<Vec<u8>, String> {
+    let mut decoded = vec![];
+    reader.take(max_bytes + 1).read_to_end(&mut decoded).map_err(|e| e.to_string())?;
+    Ok(decoded)
+}
+
+/// Removes one content coding from the body, refusing to decode it past
+/// `max_bytes`.
+fn decode(encoding: &str, body: &[u8], max_bytes: u64) -> Result<Vec<u8>, Error> {
+    let decoded = match encoding {
+        "gzip" | "x-gzip" => read_all(GzDecoder::new(body), max_bytes),
+        // `deflate` is meant to be zlib-wrapped, but some clients send raw deflate
+        "deflate" => read_all(ZlibDecoder::new(body), max_bytes).or_else(|_| read_all(DeflateDecoder::new(body), max_bytes)),
+        "br" => read_all(brotli::Decompressor::new(body, 4096), max_bytes),
+        "zstd" => ruzstd::decoding::StreamingDecoder::new(body)
+            .map_err(|e| e.to_string())
+            .and_then(|decoder| read_all(decoder, max_bytes)),
+        _ => return Err(Error::UnsupportedMediaType(format!("Unsupported Content-Encoding: {}", encoding))),
+    };
+    let decoded = decoded.map_err(|e| Error::BadRequest(format!("Invalid {} body: {}", encoding, e)))?;
+    if decoded.len() as u64 > max_bytes {
+        return Err(Error::PayloadTooLarge(format!(
+            "Decoded {} body is over max_body_bytes ({})", encoding, max_bytes)));
+    }
+    Ok(decoded)
+}
+
+/// Decodes a body sent with a Content-Encoding, in place. Returns the codings
+/// that were removed, in the order they were applied, or None when the body
+/// was not encoded. Each decoded stage is bound by `max_bytes`, as the raw
+/// body is by the limits.
+pub fn decode_body(req: &mut Request, max_bytes: u64) -> Result<Option<Vec<String>>, Error> {
+    let values = req.get_header_all(header::CONTENT_ENCODING)
+        .map(|v| v.to_str())
+        .collect::<Result<Vec<&str>, _>>()
+        .map_err(|_| Error::BadRequest(String::from("Content-Encoding is not valid UTF-8")))?;
+    let encodings: Vec<String> = values.iter()
+        .flat_map(|v| v.split(','))
+        .map(|e| e.trim().to_ascii_lowercase())
+        .filter(|e| !e.is_empty() && e != "identity")
+        .collect();
+    if encodings.is_empty() {
+        return Ok(None);
+    }
+
+    // Codings are listed in the order they were applied, so they are removed last first
+    let body = encodings.iter()
+        .rev()
+        .try_fold(req.take_body_bytes(), |body, encoding| decode(encoding, &body, max_bytes))?;
+    req.set_body(body);
+    Ok(Some(encodings))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut enc = GzEncoder::new(vec![], Compression::default());
+        enc.write_all(body).unwrap();
+        enc.finish().unwrap()
+    }
+
+    const MAX: u64 = 1024;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("gzip", &gzip(b"foo"), MAX).unwrap(), b"foo");
+
+        let mut enc = ZlibEncoder::new(vec![], Compression::default());
+        enc.write_all(b"foo").unwrap();
+        assert_eq!(decode("deflate", &enc.finish().unwrap(), MAX).unwrap(), b"foo");
+
+        let mut br = vec![];
+        brotli::BrotliCompress(&mut &b"foo"[..], &mut br, &brotli::enc::BrotliEncoderParams::default()).unwrap();
+        assert_eq!(decode("br", &br, MAX).unwrap(), b"foo");
+
+        let zstd = ruzstd::encoding::compress_to_vec(&b"foo"[..], ruzstd::encoding::CompressionLevel::Fastest);
+        assert_eq!(decode("zstd", &zstd, MAX).unwrap(), b"foo");
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(matches!(decode("gzip", b"foo", MAX), Err(Error::BadRequest(_))));
+        assert!(matches!(decode("compress", b"foo", MAX), Err(Error::UnsupportedMediaType(_))));
+    }
+
+    #[test]
+    fn test_decode_too_large() {
+        let bomb = gzip(&vec![0; MAX as usize + 1]);
+        assert!(matches!(decode("gzip", &bomb, MAX), Err(Error::PayloadTooLarge(_))));
+        assert_eq!(decode("gzip", &gzip(&vec![0; MAX as usize]), MAX).unwrap().len(), MAX as usize);
+    }
+
+    #[test]
+    fn test_decode_body() {
+        let mut req = Request::post("http://example.com/post")
+            .with_header("content-encoding", "gzip, gzip")
+            .with_body(gzip(&gzip(b"foo")));
+        assert_eq!(decode_body(&mut req, MAX).unwrap(), Some(vec![String::from("gzip"), String::from("gzip")]));
+        assert_eq!(req.into_body_str(), "foo");
+
+        let mut req = Request::post("http://example.com/post")
+            .with_body("foo");
+        assert_eq!(decode_body(&mut req, MAX).unwrap(), None);
+
+        let mut req = Request::post("http://example.com/post")
+            .with_header("content-encoding", fastly::http::HeaderValue::from_bytes(b"gz\xffip").unwrap())
+            .with_body("foo");
+        assert!(matches!(decode_body(&mut req, MAX), Err(Error::BadRequest(_))));
+    }
+}
@@ -0,0 +1,93 @@
+use fastly::device_detection::{lookup, Device};
+use fastly::{Request, Response};
+use crate::error::Error;
+use crate::format;
+use serde_json::{json, Value};
+use crate::router::Params;
+use crate::user_agent::is_bot;
+
+fn device_to_value(user_agent: &str, device: Option<&Device>) -> Value {
+    json!({
+        "user_agent": user_agent,
+        "name": device.and_then(Device::device_name),
+        "brand": device.and_then(Device::brand),
+        "model": device.and_then(Device::model),
+        "hwtype": device.and_then(Device::hwtype),
+        "is_desktop": device.and_then(Device::is_desktop),
+        "is_mobile": device.and_then(Device::is_mobile),
+        "is_tablet": device.and_then(Device::is_tablet),
+        "is_smarttv": device.and_then(Device::is_smarttv),
+        "is_tvplayer": device.and_then(Device::is_tvplayer),
+        "is_gameconsole": device.and_then(Device::is_gameconsole),
+        "is_mediaplayer": device.and_then(Device::is_mediaplayer),
+        "is_ereader": device.and_then(Device::is_ereader),
+        "is_touchscreen": device.and_then(Device::is_touchscreen),
+        "is_bot": is_bot(user_agent),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/device",
+    tag = "Request inspection",
+    params(
+        ("ua" = Option<String>, Query, description = "User-Agent to look up, the request's by default"),
+    ),
+    responses(
+        (status = 200, description = "The device the User-Agent belongs to. Unknown fields are null.", content_type = "application/json"),
+        (status = 400, description = "No User-Agent to look up.", content_type = "application/problem+json"),
+    )
+)]
+/// Runs Fastly device detection on the request's User-Agent, or on the given one.
+pub fn device(req: &Request, _: &Params) -> Result<Response, Error> {
+    let arg_pairs: Vec<(String, String)> = req.get_query().unwrap_or_default();
+    let user_agent = arg_pairs.into_iter()
+        .find_map(|(k, v)| (k == "ua").then_some(v))
+        .or_else(|| req.get_header("user-agent").and_then(|v| v.to_str().ok()).map(String::from))
+        .ok_or_else(|| Error::BadRequest(String::from("No UTF-8 User-Agent header or ua parameter")))?;
+    let device = lookup(&user_agent);
+    format::echo(req, &device_to_value(&user_agent, device.as_ref()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastly::http::StatusCode;
+
+    #[test]
+    fn test_device() {
+        // See local_server.device_detection in fastly.toml
+        let req = &Request::from_client()
+            .with_path("/device")
+            .with_header("user-agent", "Mozilla/5.0 (Linux; Android 13; SM-X700) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/118.0.0.0 Safari/537.36");
+        let resp = device(req, &Params::default()).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::OK);
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["brand"], "Samsung");
+        assert_eq!(v["is_tablet"], true);
+        assert_eq!(v["is_mobile"], false);
+        assert_eq!(v["is_bot"], false);
+    }
+
+    #[test]
+    fn test_unknown_device() {
+        let req = &Request::from_client()
+            .with_path("/device")
+            .with_query_str("ua=curl%2F8.4.0");
+        let resp = device(req, &Params::default()).unwrap();
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["user_agent"], "curl/8.4.0");
+        assert_eq!(v["name"], Value::Null);
+        assert_eq!(v["is_bot"], true);
+    }
+
+    #[test]
+    fn test_no_user_agent() {
+        let req = &Request::get("http://example.com/device");
+        assert!(matches!(device(req, &Params::default()), Err(Error::BadRequest(_))));
+
+        let req = &Request::get("http://example.com/device")
+            .with_header("user-agent", fastly::http::HeaderValue::from_bytes(b"Mozilla/5.0 \xff").unwrap());
+        assert!(matches!(device(req, &Params::default()), Err(Error::BadRequest(_))));
+    }
+}
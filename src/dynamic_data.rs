@@ -1,10 +1,12 @@
 use fastly::http::StatusCode;
-use fastly::{Error, mime, Request, Response};
+use fastly::{mime, Request, Response};
+use crate::error::Error;
 use serde_json::{json, to_string_pretty};
 use uuid::Uuid;
 use std::{thread, time};
-use regex_lite::Regex;
-use crate::utils::{req_to_json, req_with_body_to_json};
+use crate::router::Params;
+use crate::format;
+use crate::utils::{req_to_value, req_with_body_to_value};
 use base64::{Engine as _, engine::general_purpose};
 
 #[utoipa::path(
@@ -16,7 +18,7 @@ use base64::{Engine as _, engine::general_purpose};
     )
 )]
 /// Return a UUID4.
-pub fn uuid(_: &Request) -> Result<Response, Error> {
+pub fn uuid(_: &Request, _: &Params) -> Result<Response, Error> {
     let resp = json!({
         "uuid": Uuid::new_v4().to_string(),
     });
@@ -30,77 +32,66 @@ pub fn uuid(_: &Request) -> Result<Response, Error> {
     path = "/base64/{value}",
     tag = "Dynamic data",
     params(
-        ("value" = u16, Path, description = "String in base64 to decode."),
+        ("value" = String, Path, description = "String in base64 to decode."),
     ),
     responses(
         (status = 200, description = "Decoded base64 content.", content_type = "text/html"),
     )
 )]
 /// Decodes base64-encoded string.
-pub fn base64(req: &Request) -> Result<Response, Error> {
-    let caps = Regex::new(r"/base64/([A-Za-z0-9+/=]{1,4096})$")?
-        .captures(req.get_path());
-    if let Some(caps) = caps {
-        let b64 = caps.get(1).map(|m| m.as_str().as_bytes()).unwrap();
-        return match general_purpose::STANDARD.decode(&b64) {
-            Ok(decoded) => Ok(Response::from_status(StatusCode::OK)
-                .with_content_type(mime::APPLICATION_JSON)
-                .with_body(decoded)),
-            Err(_) => Ok(Response::from_status(StatusCode::BAD_REQUEST)
-                .with_content_type(mime::TEXT_PLAIN)
-                .with_body("Provided data not in base64 format. Try SFRUUEJJTiBpcyBhd2Vzb21l")),
-        }
-    }
+pub fn base64(_: &Request, params: &Params) -> Result<Response, Error> {
+    let b64 = params.get("value")
+        .filter(|v| v.len() <= 4096)
+        .ok_or_else(|| Error::BadRequest(String::from("Could not extract base64 data")))?;
+    let decoded = general_purpose::STANDARD.decode(b64)
+        .map_err(|_| Error::BadRequest(String::from("Provided data not in base64 format. Try SFRUUEJJTiBpcyBhd2Vzb21l")))?;
 
-    Ok(Response::from_status(StatusCode::BAD_REQUEST)
-        .with_content_type(mime::TEXT_HTML)
-        .with_body("Could not extract base64 data"))
+    Ok(Response::from_status(StatusCode::OK)
+        .with_content_type(mime::APPLICATION_JSON)
+        .with_body(decoded))
 }
 
-pub fn delay(req: &Request, body: String) -> Result<Response, Error> {
-    let caps = Regex::new(r"/delay/(\d{1,2})$").unwrap()
-        .captures(req.get_path());
-    if let Some(caps) = caps {
-        let mut n = caps.get(1).map_or(404, |m| m.as_str().parse::<u64>().unwrap_or(404));
-        if n > 10 {
-            n = 10;
-        }
-        let d = time::Duration::from_secs(n);
-        thread::sleep(d);
-        return Ok(Response::from_status(StatusCode::OK)
-            .with_content_type(mime::APPLICATION_JSON)
-            .with_body(body));
-    }
-
-    Ok(Response::from_status(StatusCode::NOT_FOUND)
-        .with_content_type(mime::TEXT_HTML))
+pub fn delay(params: &Params) -> Result<(), Error> {
+    let n = params.parse::<u64>("n")
+        .filter(|n| *n < 100)
+        .ok_or_else(|| Error::NotFound(String::from("Delay must be a number of seconds")))?;
+    thread::sleep(time::Duration::from_secs(n.min(10)));
+    Ok(())
 }
 
 #[utoipa::path(
     get,
-    path = "/delay/{n]",
+    path = "/delay/{n}",
     tag = "Dynamic data",
+    params(
+        ("n" = u8, Path, description = "Delay in seconds. Max: 10"),
+    ),
     responses(
         (status = 200, description = "A delayed response", content_type = "application/json"),
     )
 )]
 /// Returns a delayed response (max 10s)
-pub fn delay_get(req: &Request) -> Result<Response, Error> {
-    delay(req, req_to_json(req))
+pub fn delay_get(req: &Request, params: &Params) -> Result<Response, Error> {
+    delay(params)?;
+    format::echo(req, &req_to_value(req))
 }
 
 #[utoipa::path(
     post,
-    path = "/delay/{n]",
+    path = "/delay/{n}",
     tag = "Dynamic data",
+    params(
+        ("n" = u8, Path, description = "Delay in seconds. Max: 10"),
+    ),
     responses(
         (status = 200, description = "A delayed response", content_type = "application/json"),
     )
 )]
 /// Returns a delayed response (max 10s)
-pub fn delay_post(req: &mut Request) -> Result<Response, Error> {
-    let body = req_with_body_to_json(req);
-    delay(req, body)
+pub fn delay_post(req: &mut Request, params: &Params) -> Result<Response, Error> {
+    let value = req_with_body_to_value(req)?;
+    delay(params)?;
+    format::echo(req, &value)
 }
 
 #[utoipa::path(
@@ -115,21 +106,18 @@ pub fn delay_post(req: &mut Request) -> Result<Response, Error> {
     )
 )]
 /// Returns n random bytes
-pub fn bytes(req: &Request) -> Result<Response, Error> {
-    let caps = Regex::new(r"/bytes/(\d{1,5})$").unwrap()
-        .captures(req.get_path());
-    if let Some(caps) = caps {
-        let n = caps.get(1).map_or(100, |m| m.as_str().parse::<usize>().unwrap_or(100));
-        let mut resp:Vec<u8> = vec![0u8; n];
-        getrandom::fill(&mut resp)?;
-        return Ok(Response::from_status(StatusCode::OK)
-            .with_content_type(mime::APPLICATION_OCTET_STREAM)
-            .with_body_octet_stream(&resp));
-    }
+pub fn bytes(_: &Request, params: &Params) -> Result<Response, Error> {
+    let n = params.parse::<usize>("n")
+        .filter(|n| *n <= 99999)
+        .ok_or_else(|| Error::NotFound(String::from("Number of bytes must be between 0 and 99999")))?;
+    let mut resp:Vec<u8> = vec![0u8; n];
+    getrandom::fill(&mut resp).map_err(|e| Error::Internal(e.to_string()))?;
 
-    Ok(Response::from_status(StatusCode::NOT_FOUND)
-        .with_content_type(mime::APPLICATION_OCTET_STREAM))
+    Ok(Response::from_status(StatusCode::OK)
+        .with_content_type(mime::APPLICATION_OCTET_STREAM)
+        .with_body_octet_stream(&resp))
 }
+
 #[cfg(test)]
 mod test {
     use serde_json::Value;
@@ -139,7 +127,7 @@ mod test {
     fn test_uuid() {
         let req = &Request::from_client()
             .with_path("/uuid");
-        let resp = uuid(req);
+        let resp = uuid(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -154,7 +142,7 @@ mod test {
     fn test_base64() {
         let req = &Request::from_client()
             .with_path("/base64/Zm9vYmFy"); // echo -n foobar | base64
-        let resp = base64(req);
+        let resp = base64(req, &Params::from([("value", "Zm9vYmFy")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -166,7 +154,7 @@ mod test {
     fn test_bytes() {
         let req = &Request::from_client()
             .with_path("/bytes/42");
-        let resp = bytes(req);
+        let resp = bytes(req, &Params::from([("n", "42")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
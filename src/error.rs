@@ -0,0 +1,132 @@
+use fastly::http::{header, Method, StatusCode};
+use fastly::Response;
+use serde_json::{json, to_string_pretty};
+use std::fmt;
+use crate::router::allow_header;
+
+pub const APPLICATION_PROBLEM_JSON: &str = "application/problem+json";
+
+/// Errors returned by handlers, rendered as RFC 7807 `application/problem+json`.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    BadRequest(String),
+    NotFound(String),
+    MethodNotAllowed(Vec<Method>),
+    NotAcceptable(String),
+    PayloadTooLarge(String),
+    UriTooLong(String),
+    UnsupportedMediaType(String),
+    RequestHeaderFieldsTooLarge(String),
+    /// The client certificate was invalid, nginx's 495.
+    SslCertificate(String),
+    /// No client certificate was presented, nginx's 496.
+    SslCertificateRequired(String),
+    Internal(String),
+}
+
+impl Error {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            Error::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            Error::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::UriTooLong(_) => StatusCode::URI_TOO_LONG,
+            Error::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::RequestHeaderFieldsTooLarge(_) => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            Error::SslCertificate(_) => StatusCode::from_u16(495).unwrap_or(StatusCode::BAD_REQUEST),
+            Error::SslCertificateRequired(_) => StatusCode::from_u16(496).unwrap_or(StatusCode::BAD_REQUEST),
+            Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn detail(&self) -> String {
+        match self {
+            Error::BadRequest(detail)
+            | Error::NotFound(detail)
+            | Error::NotAcceptable(detail)
+            | Error::PayloadTooLarge(detail)
+            | Error::UriTooLong(detail)
+            | Error::UnsupportedMediaType(detail)
+            | Error::RequestHeaderFieldsTooLarge(detail)
+            | Error::SslCertificate(detail)
+            | Error::SslCertificateRequired(detail)
+            | Error::Internal(detail) => detail.clone(),
+            Error::MethodNotAllowed(allowed) => format!("Allowed methods: {}", allow_header(allowed)),
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            // 495 and 496 are not registered, so they have no canonical reason
+            Error::SslCertificate(_) => "SSL Certificate Error",
+            Error::SslCertificateRequired(_) => "SSL Certificate Required",
+            _ => self.status().canonical_reason().unwrap_or_default(),
+        }
+    }
+
+    pub fn into_response(self) -> Response {
+        let status = self.status();
+        let problem = json!({
+            "type": "about:blank",
+            "title": self.title(),
+            "status": status.as_u16(),
+            "detail": self.detail(),
+        });
+
+        let mut resp = Response::from_status(status)
+            .with_header(header::CONTENT_TYPE, APPLICATION_PROBLEM_JSON)
+            .with_body(to_string_pretty(&problem).unwrap_or_default());
+        if let Error::MethodNotAllowed(allowed) = &self {
+            resp.set_header(header::ALLOW, allow_header(allowed));
+        }
+        resp
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.status(), self.detail())
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_problem_json() {
+        let resp = Error::BadRequest(String::from("Invalid form body")).into_response();
+        assert_eq!(resp.get_status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.get_header_str("content-type"), Some(APPLICATION_PROBLEM_JSON));
+
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["title"], "Bad Request");
+        assert_eq!(v["status"], 400);
+        assert_eq!(v["detail"], "Invalid form body");
+    }
+
+    #[test]
+    fn test_method_not_allowed() {
+        let resp = Error::MethodNotAllowed(vec![Method::GET, Method::HEAD]).into_response();
+        assert_eq!(resp.get_status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.get_header_str("allow"), Some("GET, HEAD"));
+    }
+
+    #[test]
+    fn test_ssl_certificate_error() {
+        let resp = Error::SslCertificate(String::from("The client certificate has expired")).into_response();
+        assert_eq!(resp.get_status().as_u16(), 495);
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["title"], "SSL Certificate Error");
+
+        let resp = Error::SslCertificateRequired(String::from("No client certificate was presented")).into_response();
+        assert_eq!(resp.get_status().as_u16(), 496);
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["title"], "SSL Certificate Required");
+    }
+}
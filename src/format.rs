@@ -0,0 +1,229 @@
+use fastly::http::{header, StatusCode};
+use fastly::{Request, Response};
+use crate::compat;
+use crate::error::Error;
+use serde_json::Value;
+
+/// Formats echo responses can be rendered in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Pretty-printed JSON, the default.
+    Json,
+    CompactJson,
+    Yaml,
+    Xml,
+    MessagePack,
+    Cbor,
+}
+
+/// Media types accepted for each format, the first one being sent back.
+const MEDIA_TYPES: [(&str, Format); 10] = [
+    ("application/json", Format::Json),
+    ("application/yaml", Format::Yaml),
+    ("application/x-yaml", Format::Yaml),
+    ("text/yaml", Format::Yaml),
+    ("application/xml", Format::Xml),
+    ("text/xml", Format::Xml),
+    ("application/msgpack", Format::MessagePack),
+    ("application/x-msgpack", Format::MessagePack),
+    ("application/vnd.msgpack", Format::MessagePack),
+    ("application/cbor", Format::Cbor),
+];
+
+impl Format {
+    /// Parses the value of the `format` query parameter.
+    fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "compact" => Some(Format::CompactJson),
+            "yaml" => Some(Format::Yaml),
+            "xml" => Some(Format::Xml),
+            "msgpack" => Some(Format::MessagePack),
+            "cbor" => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json | Format::CompactJson => "application/json",
+            Format::Yaml => "application/yaml",
+            Format::Xml => "application/xml",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+
+    /// Picks the format from `?format=`, or else the Accept media type with the
+    /// highest quality that has a format. Falls back to pretty JSON.
+    pub fn negotiate(req: &Request) -> Result<Format, Error> {
+        if let Some(name) = req.get_query_parameter("format") {
+            return Format::from_name(name)
+                .ok_or_else(|| Error::BadRequest(format!(
+                    "Unknown format {}, expected json, compact, yaml, xml, msgpack or cbor", name)));
+        }
+
+        let accept = req.get_header_all(header::ACCEPT)
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<&str>>()
+            .join(",");
+        let mut best: Option<(f32, Format)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';');
+            let media_type = params.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let quality = params
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let format = MEDIA_TYPES.iter().find(|(t, _)| *t == media_type).map(|(_, f)| *f);
+            if let Some(format) = format {
+                if quality > 0.0 && best.is_none_or(|(q, _)| quality > q) {
+                    best = Some((quality, format));
+                }
+            }
+        }
+
+        Ok(best.map(|(_, f)| f).unwrap_or(Format::Json))
+    }
+
+    pub fn render(&self, value: &Value) -> Result<Vec<u8>, Error> {
+        let internal = |e: &dyn std::fmt::Display| Error::Internal(format!("Could not render the response: {}", e));
+        match self {
+            Format::Json => serde_json::to_vec_pretty(value).map_err(|e| internal(&e)),
+            Format::CompactJson => serde_json::to_vec(value).map_err(|e| internal(&e)),
+            Format::Yaml => serde_yaml::to_string(value).map(String::into_bytes).map_err(|e| internal(&e)),
+            Format::Xml => Ok(to_xml(value).into_bytes()),
+            Format::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| internal(&e)),
+            Format::Cbor => {
+                let mut out = vec![];
+                ciborium::into_writer(value, &mut out).map_err(|e| internal(&e))?;
+                Ok(out)
+            },
+        }
+    }
+}
+
+/// Renders an echo in the format negotiated for the request, reshaped as
+/// httpbin's in compatibility mode.
+pub fn echo(req: &Request, value: &Value) -> Result<Response, Error> {
+    let format = Format::negotiate(req)?;
+    let body = match compat::enabled(req) {
+        true => format.render(&compat::reshape(req, value.clone()))?,
+        false => format.render(value)?,
+    };
+    Ok(Response::from_status(StatusCode::OK)
+        .with_header(header::CONTENT_TYPE, format.content_type())
+        .with_header(header::VARY, "Accept")
+        .with_body(body))
+}
+
+/// Escapes markup, and drops the characters XML 1.0 does not allow even as
+/// character references, such as C0 controls.
+fn escape_xml(s: &str) -> String {
+    s.chars()
+        .filter(|c| matches!(c, '\t' | '\n' | '\r' | ' '..='\u{fffd}' | '\u{10000}'..))
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !name.to_ascii_lowercase().starts_with("xml")
+}
+
+/// Writes `value` as an element named after its key. Keys that are not valid
+/// XML names become `<item key="...">`, and arrays repeat the element.
+fn write_xml(out: &mut String, key: &str, value: &Value) {
+    if let Value::Array(values) = value {
+        values.iter().for_each(|v| write_xml(out, key, v));
+        return;
+    }
+
+    let (tag, open) = if is_xml_name(key) {
+        (key, key.to_string())
+    } else {
+        ("item", format!("item key=\"{}\"", escape_xml(key)))
+    };
+    match value {
+        Value::Null => out.push_str(&format!("<{}/>", open)),
+        Value::Object(map) => {
+            out.push_str(&format!("<{}>", open));
+            map.iter().for_each(|(k, v)| write_xml(out, k, v));
+            out.push_str(&format!("</{}>", tag));
+        },
+        Value::String(s) => out.push_str(&format!("<{}>{}</{}>", open, escape_xml(s), tag)),
+        v => out.push_str(&format!("<{}>{}</{}>", open, v, tag)),
+    }
+}
+
+fn to_xml(value: &Value) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    write_xml(&mut out, "response", value);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastly::http;
+    use serde_json::json;
+
+    #[test]
+    fn test_negotiate() {
+        let negotiate = |accept: &str| Format::negotiate(&Request::from_client().with_header("accept", accept)).unwrap();
+        assert_eq!(negotiate("*/*"), Format::Json);
+        assert_eq!(negotiate("application/yaml"), Format::Yaml);
+        assert_eq!(negotiate("text/html, application/xml;q=0.9, application/cbor;q=0.95"), Format::Cbor);
+        assert_eq!(negotiate("application/msgpack;q=0, text/plain"), Format::Json);
+
+        let req = Request::from_client()
+            .with_header("accept", "application/xml")
+            .with_query_str("format=compact");
+        assert_eq!(Format::negotiate(&req).unwrap(), Format::CompactJson);
+
+        let req = Request::from_client().with_query_str("format=toml");
+        assert!(Format::negotiate(&req).is_err());
+
+        let mut req = Request::from_client()
+            .with_header("accept", http::HeaderValue::from_bytes(b"text/\xff").unwrap());
+        req.append_header("accept", "application/yaml");
+        assert_eq!(Format::negotiate(&req).unwrap(), Format::Yaml);
+    }
+
+    #[test]
+    fn test_render() {
+        let value = json!({"args": {"a": ["1", "2"]}, "origin": null});
+        assert_eq!(Format::CompactJson.render(&value).unwrap(), br#"{"args":{"a":["1","2"]},"origin":null}"#);
+        assert_eq!(String::from_utf8(Format::Yaml.render(&value).unwrap()).unwrap(),
+                   "args:\n  a:\n  - '1'\n  - '2'\norigin: null\n");
+
+        let decoded: Value = rmp_serde::from_slice(&Format::MessagePack.render(&value).unwrap()).unwrap();
+        assert_eq!(decoded, value);
+        let decoded: Value = ciborium::from_reader(&Format::Cbor.render(&value).unwrap()[..]).unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn test_xml() {
+        let value = json!({"args": {"a": ["1", "2"], "a b": "<&>"}, "origin": null});
+        assert_eq!(to_xml(&value), "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <response><args><a>1</a><a>2</a><item key=\"a b\">&lt;&amp;&gt;</item></args><origin/></response>");
+
+        let value = json!({"data": "a\u{1}b\tc\u{80}"});
+        assert_eq!(to_xml(&value), "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <response><data>ab\tc\u{80}</data></response>");
+    }
+
+    #[test]
+    fn test_echo() {
+        let req = Request::from_client().with_query_str("format=xml");
+        let resp = echo(&req, &json!({})).unwrap();
+        assert_eq!(resp.get_header_str("content-type"), Some("application/xml"));
+        assert_eq!(resp.get_header_str("vary"), Some("Accept"));
+    }
+}
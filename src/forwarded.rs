@@ -0,0 +1,219 @@
+use fastly::Request;
+use serde_json::{json, Value};
+use std::net::IpAddr;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_REAL_IP: &str = "x-real-ip";
+const FORWARDED: &str = "forwarded";
+
+/// Whether the request carries any of the headers proxies use to pass on
+/// the client address.
+pub fn has_forwarding_headers(req: &Request) -> bool {
+    [X_FORWARDED_FOR, X_REAL_IP, FORWARDED].iter().any(|h| req.contains_header(*h))
+}
+
+/// Splits on `sep` outside of double-quoted strings.
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c == sep && !quoted => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Parses a node such as `192.0.2.43`, `192.0.2.43:47011` or
+/// `[2001:db8::17]:4711` into its address. Obfuscated identifiers such as
+/// `unknown` or `_hidden` are not addresses.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim();
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    match node.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().and_then(|ip| ip.parse().ok()),
+        None => node.rsplit_once(':').and_then(|(ip, _)| ip.parse().ok()),
+    }
+}
+
+fn is_private(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local(),
+        // Unique local fc00::/7 and link-local fe80::/10
+        IpAddr::V6(ip) => (ip.segments()[0] & 0xfe00) == 0xfc00 || (ip.segments()[0] & 0xffc0) == 0xfe80,
+    }
+}
+
+/// Describes a node: its address family and whether it is private or loopback.
+fn describe(node: &str) -> Value {
+    let ip = parse_node(node);
+    json!({
+        "address": node.trim(),
+        "version": ip.map(|ip| if ip.is_ipv4() { "IPv4" } else { "IPv6" }),
+        "private": ip.as_ref().is_some_and(is_private),
+        "loopback": ip.is_some_and(|ip| ip.is_loopback()),
+    })
+}
+
+/// Parses the RFC 7239 Forwarded elements into their for, by, proto and host.
+fn parse_forwarded(headers: &[&str]) -> Vec<Vec<(String, String)>> {
+    headers.iter()
+        .flat_map(|h| split_unquoted(h, ','))
+        .filter(|element| !element.trim().is_empty())
+        .map(|element| split_unquoted(element, ';')
+            .into_iter()
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().trim_matches('"').to_string()))
+            .collect())
+        .collect()
+}
+
+/// The values of a header, skipping those that are not valid UTF-8, which
+/// are listed in `warnings` under the header's `display` name.
+fn header_values<'a>(req: &'a Request, name: &str, display: &str, warnings: &mut Vec<String>) -> Vec<&'a str> {
+    req.get_header_all(name)
+        .filter_map(|v| match v.to_str() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                warnings.push(format!("{} has a value that is not valid UTF-8", display));
+                None
+            },
+        })
+        .collect()
+}
+
+/// Analyses the proxy chain the request claims to have gone through, from
+/// X-Forwarded-For, X-Real-IP and Forwarded. These headers are set by the
+/// client or the proxies in front of us and cannot be verified, so entries
+/// that look inconsistent or spoofed are listed in `warnings`.
+pub fn analyze(req: &Request) -> Value {
+    let mut warnings: Vec<String> = vec![];
+
+    let xff: Vec<String> = header_values(req, X_FORWARDED_FOR, "X-Forwarded-For", &mut warnings)
+        .iter()
+        .flat_map(|h| h.split(','))
+        .map(|node| node.trim().to_string())
+        .filter(|node| !node.is_empty())
+        .collect();
+    let real_ip = header_values(req, X_REAL_IP, "X-Real-IP", &mut warnings).first().map(|ip| ip.trim().to_string());
+    let forwarded = parse_forwarded(&header_values(req, FORWARDED, "Forwarded", &mut warnings));
+    let forwarded_for: Vec<&str> = forwarded.iter()
+        .filter_map(|element| element.iter().find(|(k, _)| k == "for").map(|(_, v)| v.as_str()))
+        .collect();
+
+    for (i, node) in xff.iter().enumerate() {
+        if parse_node(node).is_none() {
+            warnings.push(format!("X-Forwarded-For entry {} is not an IP address", node));
+        }
+        if xff[..i].contains(node) {
+            warnings.push(format!("{} appears more than once in X-Forwarded-For", node));
+        }
+    }
+    let claimed = xff.iter().map(String::as_str).chain(real_ip.as_deref()).chain(forwarded_for.iter().copied());
+    for node in claimed {
+        if parse_node(node).is_some_and(|ip| ip.is_loopback()) {
+            warnings.push(format!("{} is a loopback address, which no proxy would forward", node));
+        }
+    }
+    if let Some(real_ip) = &real_ip {
+        if parse_node(real_ip).is_none() {
+            warnings.push(format!("X-Real-IP {} is not an IP address", real_ip));
+        }
+        if !xff.is_empty() && !xff.contains(real_ip) {
+            warnings.push(format!("X-Real-IP {} is not in X-Forwarded-For", real_ip));
+        }
+    }
+    let forwarded_ips: Vec<Option<IpAddr>> = forwarded_for.iter().map(|n| parse_node(n)).collect();
+    let xff_ips: Vec<Option<IpAddr>> = xff.iter().map(|n| parse_node(n)).collect();
+    if !xff.is_empty() && !forwarded_for.is_empty() && forwarded_ips != xff_ips {
+        warnings.push(String::from("Forwarded and X-Forwarded-For list different addresses"));
+    }
+
+    json!({
+        "client": req.get_client_ip_addr().map(|ip| describe(&ip.to_string())),
+        "x_forwarded_for": xff.iter().map(|node| describe(node)).collect::<Vec<Value>>(),
+        "x_real_ip": real_ip.as_deref().map(describe),
+        "forwarded": forwarded.iter().map(|element| {
+            let get = |key: &str| element.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+            json!({
+                "for": get("for").map(describe),
+                "by": get("by").map(describe),
+                "proto": get("proto"),
+                "host": get("host"),
+            })
+        }).collect::<Vec<Value>>(),
+        "warnings": warnings,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_node() {
+        assert_eq!(parse_node("192.0.2.43"), Some("192.0.2.43".parse().unwrap()));
+        assert_eq!(parse_node("192.0.2.43:47011"), Some("192.0.2.43".parse().unwrap()));
+        assert_eq!(parse_node("2001:db8::17"), Some("2001:db8::17".parse().unwrap()));
+        assert_eq!(parse_node("[2001:db8::17]:4711"), Some("2001:db8::17".parse().unwrap()));
+        assert_eq!(parse_node("_hidden"), None);
+        assert_eq!(parse_node("unknown"), None);
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe("10.0.0.1"), json!({"address": "10.0.0.1", "version": "IPv4", "private": true, "loopback": false}));
+        assert_eq!(describe("::1"), json!({"address": "::1", "version": "IPv6", "private": false, "loopback": true}));
+        assert_eq!(describe("fd00::1")["private"], true);
+        assert_eq!(describe("_hidden")["version"], Value::Null);
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let req = Request::from_client()
+            .with_header("forwarded", r#"for="[2001:db8:cafe::17]:4711";proto=https;host="example.com", for=_hidden;by=10.0.0.1"#);
+        let v = analyze(&req);
+        assert_eq!(v["forwarded"][0]["for"]["version"], "IPv6");
+        assert_eq!(v["forwarded"][0]["proto"], "https");
+        assert_eq!(v["forwarded"][0]["host"], "example.com");
+        assert_eq!(v["forwarded"][1]["for"]["address"], "_hidden");
+        assert_eq!(v["forwarded"][1]["by"]["private"], true);
+        assert_eq!(v["warnings"], json!([]));
+    }
+
+    #[test]
+    fn test_warnings() {
+        let req = Request::from_client()
+            .with_header("x-forwarded-for", "127.0.0.1, 203.0.113.1, foo, 203.0.113.1")
+            .with_header("x-real-ip", "198.51.100.1")
+            .with_header("forwarded", "for=203.0.113.1");
+        let warnings = analyze(&req)["warnings"].clone();
+        assert_eq!(warnings, json!([
+            "X-Forwarded-For entry foo is not an IP address",
+            "203.0.113.1 appears more than once in X-Forwarded-For",
+            "127.0.0.1 is a loopback address, which no proxy would forward",
+            "X-Real-IP 198.51.100.1 is not in X-Forwarded-For",
+            "Forwarded and X-Forwarded-For list different addresses",
+        ]));
+    }
+
+    #[test]
+    fn test_not_utf8() {
+        let req = Request::from_client()
+            .with_header("x-forwarded-for", fastly::http::HeaderValue::from_bytes(b"203.0.113.1\xff").unwrap())
+            .with_header("x-real-ip", "203.0.113.1");
+        let v = analyze(&req);
+        assert_eq!(v["x_forwarded_for"], json!([]));
+        assert_eq!(v["x_real_ip"]["address"], "203.0.113.1");
+        assert_eq!(v["warnings"], json!(["X-Forwarded-For has a value that is not valid UTF-8"]));
+    }
+}
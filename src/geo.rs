@@ -0,0 +1,106 @@
+use fastly::geo::{geo_lookup, Geo, UtcOffset};
+use fastly::{Request, Response};
+use crate::error::Error;
+use crate::format;
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use crate::router::Params;
+
+/// Formats a UTC offset as in ISO 8601, e.g. `-07:00`.
+fn format_utc_offset(offset: UtcOffset) -> String {
+    let (hours, minutes, _) = offset.as_hms();
+    let sign = if offset.is_negative() { '-' } else { '+' };
+    format!("{}{:02}:{:02}", sign, hours.abs(), minutes.abs())
+}
+
+fn geo_to_value(ip: IpAddr, geo: &Geo) -> Value {
+    json!({
+        "ip": ip,
+        "country_code": geo.country_code(),
+        "country_name": geo.country_name(),
+        "continent": geo.continent().as_code(),
+        "region": geo.region(),
+        "city": geo.city(),
+        "postal_code": geo.postal_code(),
+        "latitude": geo.latitude(),
+        "longitude": geo.longitude(),
+        "as_number": geo.as_number(),
+        "as_name": geo.as_name(),
+        "conn_type": geo.conn_type(),
+        "conn_speed": geo.conn_speed(),
+        "utc_offset": geo.utc_offset().map(format_utc_offset),
+    })
+}
+
+/// Geolocation data for an address, None if Fastly has none, as for
+/// private addresses.
+pub fn lookup(ip: IpAddr) -> Option<Value> {
+    geo_lookup(ip).map(|geo| geo_to_value(ip, &geo))
+}
+
+/// The address given with `?ip=`, or else the client's.
+fn target_ip(req: &Request) -> Result<IpAddr, Error> {
+    match req.get_query_parameter("ip") {
+        Some(ip) => ip.parse::<IpAddr>()
+            .map_err(|_| Error::BadRequest(format!("{} is not an IP address", ip))),
+        None => req.get_client_ip_addr()
+            .ok_or_else(|| Error::BadRequest(String::from("The client IP address is unknown"))),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/geo",
+    tag = "Request inspection",
+    params(
+        ("ip" = Option<String>, Query, description = "IP address to locate, the client's by default"),
+    ),
+    responses(
+        (status = 200, description = "Geolocation data for the IP address.", content_type = "application/json"),
+        (status = 404, description = "No geolocation data for the IP address.", content_type = "application/problem+json"),
+    )
+)]
+/// Returns the geolocation of the client, or of the given IP address.
+pub fn geo(req: &Request, _: &Params) -> Result<Response, Error> {
+    let ip = target_ip(req)?;
+    let geo = lookup(ip)
+        .ok_or_else(|| Error::NotFound(format!("No geolocation data for {}", ip)))?;
+    format::echo(req, &geo)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastly::http::StatusCode;
+
+    #[test]
+    fn test_format_utc_offset() {
+        assert_eq!(format_utc_offset(UtcOffset::from_hms(-7, 0, 0).unwrap()), "-07:00");
+        assert_eq!(format_utc_offset(UtcOffset::from_hms(5, 30, 0).unwrap()), "+05:30");
+    }
+
+    #[test]
+    fn test_geo() {
+        // See local_server.geolocation in fastly.toml
+        let req = &Request::from_client()
+            .with_path("/geo")
+            .with_query_str("ip=127.0.0.1");
+        let resp = geo(req, &Params::default()).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::OK);
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["ip"], "127.0.0.1");
+        assert_eq!(v["country_code"], "CA");
+        assert_eq!(v["city"], "Test City");
+        assert_eq!(v["as_number"], 12345);
+        assert_eq!(v["conn_type"], "wired");
+        assert_eq!(v["utc_offset"], "-07:00");
+    }
+
+    #[test]
+    fn test_geo_invalid_ip() {
+        let req = &Request::from_client()
+            .with_path("/geo")
+            .with_query_str("ip=foo");
+        assert!(matches!(geo(req, &Params::default()), Err(Error::BadRequest(_))));
+    }
+}
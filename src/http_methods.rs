@@ -1,17 +1,18 @@
-use fastly::http::StatusCode;
-use fastly::{Error, http, mime, Request, Response};
-use crate::utils::{req_to_json, req_with_body_to_json};
+use fastly::{Request, Response};
+use crate::compat;
+use crate::error::Error;
+use crate::format;
+use crate::router::Params;
+use crate::utils::{req_to_value, req_with_body_to_value};
+use serde_json::json;
 
 fn http_methods(req: &Request) -> Result<Response, Error> {
-    Ok(Response::from_status(StatusCode::OK)
-        .with_content_type(mime::APPLICATION_JSON)
-        .with_body(req_to_json(req)))
+    format::echo(req, &req_to_value(req))
 }
 
 fn http_methods_mut(req: &mut Request) -> Result<Response, Error> {
-    Ok(Response::from_status(StatusCode::OK)
-        .with_content_type(mime::APPLICATION_JSON)
-        .with_body(req_with_body_to_json(req)))
+    let value = req_with_body_to_value(req)?;
+    format::echo(req, &value)
 }
 
 #[utoipa::path(
@@ -23,7 +24,7 @@ fn http_methods_mut(req: &mut Request) -> Result<Response, Error> {
     )
 )]
 /// The request's query parameter
-pub fn get(req: &Request) -> Result<Response, Error> {
+pub fn get(req: &Request, _: &Params) -> Result<Response, Error> {
     http_methods(req)
 }
 
@@ -36,7 +37,7 @@ pub fn get(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// The request's POST parameter
-pub fn post(req: &mut Request) -> Result<Response, Error> {
+pub fn post(req: &mut Request, _: &Params) -> Result<Response, Error> {
     http_methods_mut(req)
 }
 
@@ -49,8 +50,8 @@ pub fn post(req: &mut Request) -> Result<Response, Error> {
     )
 )]
 /// The request's PUT parameter
-pub fn put(req: &mut Request) -> Result<Response, Error> {
-    http_methods(req)
+pub fn put(req: &mut Request, _: &Params) -> Result<Response, Error> {
+    http_methods_mut(req)
 }
 
 #[utoipa::path(
@@ -62,8 +63,8 @@ pub fn put(req: &mut Request) -> Result<Response, Error> {
     )
 )]
 /// The request's PATCH parameter
-pub fn patch(req: &mut Request) -> Result<Response, Error> {
-    return http_methods(req)
+pub fn patch(req: &mut Request, _: &Params) -> Result<Response, Error> {
+    http_methods_mut(req)
 }
 
 #[utoipa::path(
@@ -75,22 +76,63 @@ pub fn patch(req: &mut Request) -> Result<Response, Error> {
     )
 )]
 /// The request's DELETE parameter
-pub fn delete(req: &Request) -> Result<Response, Error> {
-    return http_methods(req)
+pub fn delete(req: &mut Request, _: &Params) -> Result<Response, Error> {
+    http_methods_mut(req)
 }
 
 
+fn rr_anything(req: &mut Request) -> Result<Response, Error> {
+    let mut value = req_with_body_to_value(req)?;
+    value["method"] = json!(req.get_method_str());
+    // httpbin's /anything has no path
+    if !compat::enabled(req) {
+        value["path"] = json!(req.get_path());
+    }
+    format::echo(req, &value)
+}
+
+#[utoipa::path(
+    method(get, post, put, patch, delete, head, options, trace),
+    path = "/anything",
+    tag = "Anything",
+    responses(
+        (status = 200, description = "Anything passed in request", content_type = "application/json")
+    )
+)]
+/// Returns anything passed in request data, for any method.
+pub fn anything(req: &mut Request, _: &Params) -> Result<Response, Error> {
+    rr_anything(req)
+}
+
+#[utoipa::path(
+    method(get, post, put, patch, delete, head, options, trace),
+    path = "/anything/{anything}",
+    tag = "Anything",
+    params(
+        ("anything" = String, Path, description = "Any path, may contain slashes"),
+    ),
+    responses(
+        (status = 200, description = "Anything passed in request", content_type = "application/json")
+    )
+)]
+/// Returns anything passed in request data, for any method and sub-path.
+pub fn anything_path(req: &mut Request, _: &Params) -> Result<Response, Error> {
+    rr_anything(req)
+}
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use fastly::http::{self, StatusCode};
+    use fastly::mime;
     use serde_json::Value;
+    use crate::middleware::{Pipeline, RequestId};
 
     #[test]
     fn test_get() {
         let req = &Request::from_client()
             .with_path("/get");
-        let resp = get(req);
+        let resp = get(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -102,7 +144,7 @@ mod test {
         let req = &Request::from_client()
             .with_path("/get")
             .with_query_str("foo=bar&fud=baz");
-        let resp = get(req);
+        let resp = get(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -115,16 +157,108 @@ mod test {
         assert_eq!(v["url"], "http://example.com/get?foo=bar&fud=baz");
     }
 
+    #[test]
+    fn test_get_request_id() {
+        let req = &mut Request::from_client()
+            .with_header("x-request-id", "foo")
+            .with_path("/get");
+        let mut pipeline = Pipeline::new(vec![Box::new(RequestId::default())]);
+        let resp = pipeline.run(req, |req| get(req, &Params::default()).unwrap());
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["request_id"], "foo");
+    }
+
     #[test]
     fn test_delete() {
-        let req = &Request::from_client()
+        let req = &mut Request::from_client()
             .with_method(http::Method::DELETE)
             .with_path("/delete");
-        let resp = delete(req);
+        let resp = delete(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
         assert_eq!(resp.get_content_type(), Some(mime::APPLICATION_JSON));
     }
 
+    #[test]
+    fn test_get_format() {
+        let req = &Request::from_client()
+            .with_header("accept", "application/yaml")
+            .with_path("/get");
+        let resp = get(req, &Params::default()).unwrap();
+        assert_eq!(resp.get_header_str("content-type"), Some("application/yaml"));
+        assert!(resp.into_body_str().contains("url: http://example.com/get"));
+    }
+
+    #[test]
+    fn test_put_body() {
+        let req = &mut Request::from_client()
+            .with_method(http::Method::PUT)
+            .with_path("/put")
+            .with_body_text_plain("foo");
+        let resp = put(req, &Params::default()).unwrap();
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["data"], "foo");
+        assert_eq!(v["json"], Value::Null);
+    }
+
+    #[test]
+    fn test_patch_form() {
+        let req = &mut Request::from_client()
+            .with_method(http::Method::PATCH)
+            .with_path("/patch")
+            .with_header("content-type", "application/x-www-form-urlencoded")
+            .with_body("foo=bar");
+        let resp = patch(req, &Params::default()).unwrap();
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["form"]["foo"], "bar");
+        assert_eq!(v["data"], "");
+    }
+
+    #[test]
+    fn test_delete_json() {
+        let req = &mut Request::from_client()
+            .with_method(http::Method::DELETE)
+            .with_path("/delete")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"foo": "bar"}"#);
+        let resp = delete(req, &Params::default()).unwrap();
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["json"]["foo"], "bar");
+        assert_eq!(v["data"], r#"{"foo": "bar"}"#);
+    }
+
+    #[test]
+    fn test_delete_compat() {
+        let req = &mut Request::from_client()
+            .with_method(http::Method::DELETE)
+            .with_header("x-httpbin-compat", "1")
+            .with_path("/delete");
+        let mut pipeline = Pipeline::new(vec![Box::new(RequestId::default())]);
+        let resp = pipeline.run(req, |req| delete(req, &Params::default()).unwrap());
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        let keys: Vec<&String> = v.as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["args", "data", "files", "form", "headers", "json", "origin", "url"]);
+        assert!(v["headers"].get("X-Request-Id").is_none());
+    }
+
+    #[test]
+    fn test_anything() {
+        let mut req = Request::from_client()
+            .with_method(http::Method::from_bytes(b"PROPFIND").unwrap())
+            .with_path("/anything/foo/bar")
+            .with_query_str("foo=bar");
+        let resp = anything_path(&mut req, &Params::from([("anything", "foo/bar")]));
+        assert!(resp.is_ok());
+        let resp = resp.unwrap();
+        assert_eq!(resp.get_status(), StatusCode::OK);
+        assert_eq!(resp.get_content_type(), Some(mime::APPLICATION_JSON));
+
+        let body = resp.into_body_str();
+        let v: Value = serde_json::from_str(body.as_str()).unwrap();
+        assert_eq!(v["method"], "PROPFIND");
+        assert_eq!(v["path"], "/anything/foo/bar");
+        assert_eq!(v["args"]["foo"], "bar");
+        assert_eq!(v["url"], "http://example.com/anything/foo/bar?foo=bar");
+    }
 }
@@ -1,6 +1,7 @@
-use fastly::{Error, mime, Request, Response};
+use fastly::{mime, Request, Response};
+use crate::error::Error;
 use fastly::http::HeaderValue;
-use crate::StatusCode;
+use crate::router::Params;
 
 #[utoipa::path(
     get,
@@ -11,8 +12,8 @@ use crate::StatusCode;
     )
 )]
 /// Returns a simple JPEG image.
-pub fn jpeg(_: &Request) -> Result<Response, Error> {
-    return crate::assets::serve("jpeg.jpeg", mime::IMAGE_JPEG);
+pub fn jpeg(_: &Request, _: &Params) -> Result<Response, Error> {
+    crate::assets::serve("jpeg.jpeg", mime::IMAGE_JPEG)
 }
 
 #[utoipa::path(
@@ -24,8 +25,8 @@ pub fn jpeg(_: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns a simple PNG image.
-pub fn png(_: &Request) -> Result<Response, Error> {
-    return crate::assets::serve("png.png", mime::IMAGE_PNG);
+pub fn png(_: &Request, _: &Params) -> Result<Response, Error> {
+    crate::assets::serve("png.png", mime::IMAGE_PNG)
 }
 
 #[utoipa::path(
@@ -37,8 +38,8 @@ pub fn png(_: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns a simple SVG image.
-pub fn svg(_: &Request) -> Result<Response, Error> {
-    return crate::assets::serve("svg.svg", mime::IMAGE_SVG);
+pub fn svg(_: &Request, _: &Params) -> Result<Response, Error> {
+    crate::assets::serve("svg.svg", mime::IMAGE_SVG)
 }
 
 #[utoipa::path(
@@ -50,7 +51,7 @@ pub fn svg(_: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns a simple WEBP image.
-pub fn webp(_: &Request) -> Result<Response, Error> {
+pub fn webp(_: &Request, _: &Params) -> Result<Response, Error> {
     let mime_webp: mime::Mime = "image/webp".parse().unwrap_or(mime::APPLICATION_OCTET_STREAM);
     crate::assets::serve("webp.webp", mime_webp)
 }
@@ -64,7 +65,7 @@ pub fn webp(_: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns a simple image of the type suggest by the Accept header.
-pub fn image(req: &Request) -> Result<Response, Error> {
+pub fn image(req: &Request, params: &Params) -> Result<Response, Error> {
     // reproduced logic from https://github.com/postmanlabs/httpbin/blob/f8ec666b4d1b654e4ff6aedd356f510dcac09f83/httpbin/core.py#L1645
     let default = &HeaderValue::from_static("image/png");
     let accept = req
@@ -73,17 +74,16 @@ pub fn image(req: &Request) -> Result<Response, Error> {
         .to_str()
         .unwrap_or_default();
     if accept.contains("image/webp") {
-        return webp(req)
+        return webp(req, params)
     }
     if accept.contains("image/svg+xml") {
-        return svg(req)
+        return svg(req, params)
     }
     if accept.contains("image/jpeg") {
-        return jpeg(req)
+        return jpeg(req, params)
     }
     if accept.contains("image/png") || accept.contains("image/*") {
-        return png(req)
+        return png(req, params)
     }
-    Ok(Response::from_status(StatusCode::NOT_ACCEPTABLE)
-        .with_content_type(mime::APPLICATION_JSON))
+    Err(Error::NotAcceptable(format!("No image available for Accept: {}", accept)))
 }
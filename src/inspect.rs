@@ -0,0 +1,259 @@
+use base64::{Engine as _, engine::general_purpose};
+use fastly::{Request, Response};
+use crate::error::Error;
+use crate::format;
+use crate::request_inspection::version_name;
+use crate::router::Params;
+use crate::utils::{media_type, multi_map, sha256_hex};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Signatures of binary formats: the offset they are found at, the bytes, and
+/// the media type.
+const MAGIC_BYTES: [(usize, &[u8], &str); 18] = [
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (8, b"WEBP", "image/webp"),
+    (0, b"\x00\x00\x01\x00", "image/x-icon"),
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"\x1f\x8b", "application/gzip"),
+    (0, b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (0, b"BZh", "application/x-bzip2"),
+    (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (0, b"\x00asm", "application/wasm"),
+    (0, b"\x7fELF", "application/x-executable"),
+    (0, b"OggS", "audio/ogg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"ID3", "audio/mpeg"),
+    (4, b"ftyp", "video/mp4"),
+];
+
+/// Guesses the media type of a body from its first bytes, ignoring the
+/// Content-Type the client declared. Text is told apart by its first
+/// characters. None for an empty body.
+fn sniff(body: &[u8]) -> Option<&'static str> {
+    if body.is_empty() {
+        return None;
+    }
+    if let Some((_, _, media_type)) = MAGIC_BYTES.iter().find(|(offset, magic, _)| body.get(*offset..).is_some_and(|b| b.starts_with(magic))) {
+        return Some(media_type);
+    }
+    let Ok(text) = std::str::from_utf8(body) else {
+        return Some("application/octet-stream");
+    };
+    let start = text.trim_start().chars().take(15).collect::<String>().to_ascii_lowercase();
+    Some(match start {
+        _ if serde_json::from_str::<Value>(text).is_ok() => "application/json",
+        s if s.starts_with("<?xml") => "application/xml",
+        s if s.starts_with("<!doctype html") || s.starts_with("<html") => "text/html",
+        s if s.starts_with("<svg") => "image/svg+xml",
+        _ => "text/plain",
+    })
+}
+
+/// Decodes %XX escapes, leaving malformed ones as they are.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            },
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A header line. A value that is not valid UTF-8 is shown lossily, with the
+/// bytes it was sent as in `value_base64`.
+fn header_to_value(name: &str, value: &[u8]) -> Value {
+    match std::str::from_utf8(value) {
+        Ok(value) => json!({"name": name, "value": value}),
+        Err(_) => json!({
+            "name": name,
+            "value": String::from_utf8_lossy(value),
+            "value_base64": general_purpose::STANDARD.encode(value),
+        }),
+    }
+}
+
+/// The headers in the order and case the client sent them. Headers that
+/// were not in the client request, such as those of a request built in
+/// tests, follow in the order Fastly keeps them.
+fn ordered_headers(req: &Request) -> Vec<Value> {
+    let mut names: Vec<String> = req.get_original_header_names().map(Iterator::collect).unwrap_or_default();
+    for name in req.get_header_names() {
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(name.as_str())) {
+            names.push(name.to_string());
+        }
+    }
+
+    // A header sent several times is listed once per line among the original
+    // names; the last of them takes the values left.
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    names.iter().for_each(|n| *counts.entry(n.to_ascii_lowercase()).or_default() += 1);
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut headers = vec![];
+    for name in names {
+        let values = req.get_header_all(name.as_str()).collect::<Vec<_>>();
+        let occurrence = seen.entry(name.to_ascii_lowercase()).or_default();
+        let values = match *occurrence + 1 == counts[&name.to_ascii_lowercase()] {
+            true => values.get(*occurrence..).unwrap_or_default(),
+            false => values.get(*occurrence..*occurrence + 1).unwrap_or_default(),
+        };
+        *occurrence += 1;
+        headers.extend(values.iter().map(|value| header_to_value(&name, value.as_bytes())));
+    }
+    headers
+}
+
+/// Where the request is served from, from the environment Compute sets.
+fn server() -> Value {
+    let env = |name: &str| std::env::var(name).ok();
+    json!({
+        "pop": env("FASTLY_POP"),
+        "region": env("FASTLY_REGION"),
+        "hostname": env("FASTLY_HOSTNAME"),
+        "service_version": env("FASTLY_SERVICE_VERSION"),
+    })
+}
+
+#[utoipa::path(
+    method(get, post, put, patch, delete, head, options, trace),
+    path = "/inspect",
+    tag = "Request inspection",
+    responses(
+        (status = 200, description = "Everything RestReflect sees of the request. Fields unavailable for it, such as TLS on plain HTTP, are null.", content_type = "application/json")
+    )
+)]
+/// Returns everything known about the request: request line, headers as sent,
+/// connection, body fingerprint, the POP serving it and timing.
+pub fn inspect(req: &mut Request, _: &Params) -> Result<Response, Error> {
+    let started_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let start = Instant::now();
+    let body = req.take_body_bytes();
+    let body_read = start.elapsed();
+
+    let arg_pairs: Vec<(String, String)> = req.get_query().unwrap_or_default();
+    let resp = json!({
+        "method": req.get_method_str(),
+        "url": req.get_url_str(),
+        "path": req.get_path(),
+        "decoded_path": percent_decode(req.get_path()),
+        "query_string": req.get_query_str(),
+        "args": multi_map(arg_pairs.into_iter().map(|(k, v)| (k, json!(v)))),
+        "http_version": version_name(req),
+        "headers": ordered_headers(req),
+        "client": {
+            "ip": req.get_client_ip_addr(),
+            "server_ip": req.get_server_ip_addr(),
+        },
+        "tls": {
+            "protocol": req.get_tls_protocol(),
+            "cipher": req.get_tls_cipher_openssl_name(),
+        },
+        "body": {
+            "size": body.len(),
+            "sha256": sha256_hex(&body),
+            "declared_type": media_type(req).map(|m| m.essence_str().to_string()),
+            "detected_type": sniff(&body),
+        },
+        "server": server(),
+        // Measured from when the handler started, so routing and middlewares
+        // are left out: Server-Timing has the total
+        "timing": {
+            "handler_started_at_ms": started_at.as_millis() as u64,
+            "body_read_ms": body_read.as_secs_f64() * 1000.0,
+            "handler_elapsed_ms": start.elapsed().as_secs_f64() * 1000.0,
+        },
+    });
+
+    format::echo(req, &resp)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastly::http::StatusCode;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b""), None);
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR"), Some("image/png"));
+        assert_eq!(sniff(b"RIFF\x24\x00\x00\x00WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff(b"\x1f\x8b\x08\x00"), Some("application/gzip"));
+        assert_eq!(sniff(b" {\"a\": 1}"), Some("application/json"));
+        assert_eq!(sniff(b"<?xml version=\"1.0\"?><a/>"), Some("application/xml"));
+        assert_eq!(sniff(b"<!DOCTYPE html><html></html>"), Some("text/html"));
+        assert_eq!(sniff(b"hello"), Some("text/plain"));
+        assert_eq!(sniff(b"\xff\xfe\x00"), Some("application/octet-stream"));
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("/a%20b/%C3%A9"), "/a b/é");
+        assert_eq!(percent_decode("/100%/%zz"), "/100%/%zz");
+    }
+
+    #[test]
+    fn test_ordered_headers() {
+        let mut req = Request::get("http://example.com/inspect")
+            .with_header("x-b", "1")
+            .with_header("x-a", "2");
+        req.append_header("x-b", "3");
+        assert_eq!(ordered_headers(&req), vec![
+            json!({"name": "x-b", "value": "1"}),
+            json!({"name": "x-b", "value": "3"}),
+            json!({"name": "x-a", "value": "2"}),
+        ]);
+    }
+
+    #[test]
+    fn test_ordered_headers_not_utf8() {
+        let req = Request::get("http://example.com/inspect")
+            .with_header("x-a", fastly::http::HeaderValue::from_bytes(b"caf\xe9").unwrap());
+        assert_eq!(ordered_headers(&req), vec![
+            json!({"name": "x-a", "value": "caf\u{fffd}", "value_base64": "Y2Fm6Q=="}),
+        ]);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let req = &mut Request::post("http://example.com/inspect%20me?a=1&a=2")
+            .with_header("content-type", "text/plain; charset=utf-8")
+            .with_body("abc");
+        let resp = inspect(req, &Params::default()).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::OK);
+        let v: Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["method"], "POST");
+        assert_eq!(v["path"], "/inspect%20me");
+        assert_eq!(v["decoded_path"], "/inspect me");
+        assert_eq!(v["query_string"], "a=1&a=2");
+        assert_eq!(v["args"], json!({"a": ["1", "2"]}));
+        assert_eq!(v["http_version"], "1.1");
+        assert_eq!(v["tls"], json!({"protocol": null, "cipher": null}));
+        assert!(v["timing"]["handler_started_at_ms"].as_u64().unwrap() > 0);
+        assert!(v["timing"]["handler_elapsed_ms"].as_f64().unwrap() >= v["timing"]["body_read_ms"].as_f64().unwrap());
+        assert_eq!(v["body"], json!({
+            "size": 3,
+            "sha256": "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            "declared_type": "text/plain",
+            "detected_type": "text/plain",
+        }));
+    }
+}
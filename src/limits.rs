@@ -0,0 +1,159 @@
+use fastly::http::header;
+use fastly::{Request, Response};
+use crate::error::Error;
+use crate::middleware::Middleware;
+use std::io::Read;
+
+/// Bounds on the size of requests, each configurable from the config store
+/// under the same name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    pub max_body_bytes: u64,
+    pub max_url_bytes: usize,
+    pub max_header_count: usize,
+    /// Total size of the header names and values.
+    pub max_header_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_body_bytes: 8 * 1024 * 1024,
+            max_url_bytes: 8 * 1024,
+            max_header_count: 100,
+            max_header_bytes: 64 * 1024,
+        }
+    }
+}
+
+impl Limits {
+    fn check_headers(&self, req: &Request) -> Result<(), Error> {
+        let url_bytes = req.get_url_str().len();
+        if url_bytes > self.max_url_bytes {
+            return Err(Error::UriTooLong(format!(
+                "URL is {} bytes, over max_url_bytes ({})", url_bytes, self.max_url_bytes)));
+        }
+
+        let header_count = req.get_headers().count();
+        if header_count > self.max_header_count {
+            return Err(Error::RequestHeaderFieldsTooLarge(format!(
+                "{} headers, over max_header_count ({})", header_count, self.max_header_count)));
+        }
+
+        let header_bytes: usize = req.get_headers().map(|(k, v)| k.as_str().len() + v.len()).sum();
+        if header_bytes > self.max_header_bytes {
+            return Err(Error::RequestHeaderFieldsTooLarge(format!(
+                "Headers are {} bytes, over max_header_bytes ({})", header_bytes, self.max_header_bytes)));
+        }
+
+        Ok(())
+    }
+
+    /// Rejects bodies over the limit from their Content-Length, or by reading
+    /// at most one byte past the limit when it is missing.
+    fn check_body(&self, req: &mut Request) -> Result<(), Error> {
+        let too_large = |size: u64| Error::PayloadTooLarge(format!(
+            "Body is {} bytes, over max_body_bytes ({})", size, self.max_body_bytes));
+
+        let content_length = req.get_header(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        match content_length {
+            Some(size) if size > self.max_body_bytes => Err(too_large(size)),
+            Some(_) => Ok(()),
+            None if !req.has_body() => Ok(()),
+            None => {
+                let mut body = vec![];
+                req.take_body()
+                    .take(self.max_body_bytes + 1)
+                    .read_to_end(&mut body)
+                    .map_err(|e| Error::BadRequest(format!("Could not read body: {}", e)))?;
+                if body.len() as u64 > self.max_body_bytes {
+                    return Err(Error::PayloadTooLarge(format!(
+                        "Body is over max_body_bytes ({})", self.max_body_bytes)));
+                }
+                req.set_body(body);
+                Ok(())
+            },
+        }
+    }
+}
+
+impl Middleware for Limits {
+    fn name(&self) -> &'static str {
+        "limits"
+    }
+
+    fn before(&mut self, req: &mut Request) -> Option<Response> {
+        self.check_headers(req)
+            .and_then(|_| self.check_body(req))
+            .err()
+            .map(Error::into_response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastly::http::StatusCode;
+
+    fn limits() -> Limits {
+        Limits {
+            max_body_bytes: 4,
+            max_url_bytes: 32,
+            max_header_count: 2,
+            max_header_bytes: 16,
+        }
+    }
+
+    #[test]
+    fn test_within_limits() {
+        let mut req = Request::post("http://example.com/post")
+            .with_body("foo");
+        assert_eq!(limits().before(&mut req).map(|r| r.get_status()), None);
+        assert_eq!(req.into_body_str(), "foo");
+    }
+
+    #[test]
+    fn test_body_too_large() {
+        let mut req = Request::post("http://example.com/post")
+            .with_body("foobar");
+        let resp = limits().before(&mut req).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(resp.into_body_str().contains("max_body_bytes"));
+    }
+
+    #[test]
+    fn test_invalid_content_length() {
+        let mut req = Request::post("http://example.com/post")
+            .with_header(header::CONTENT_LENGTH, fastly::http::HeaderValue::from_bytes(b"\xff").unwrap())
+            .with_body("foobar");
+        let resp = limits().before(&mut req).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_url_too_long() {
+        let mut req = Request::get("http://example.com/get?foo=barbarbarbar");
+        let resp = limits().before(&mut req).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::URI_TOO_LONG);
+        assert!(resp.into_body_str().contains("max_url_bytes"));
+    }
+
+    #[test]
+    fn test_too_many_headers() {
+        let mut req = Request::get("http://example.com/get")
+            .with_header("a", "1")
+            .with_header("b", "2")
+            .with_header("c", "3");
+        let resp = limits().before(&mut req).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+        assert!(resp.into_body_str().contains("max_header_count"));
+
+        let mut req = Request::get("http://example.com/get")
+            .with_header("foo", "barbarbarbarbar");
+        let resp = limits().before(&mut req).unwrap();
+        assert_eq!(resp.get_status(), StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+        assert!(resp.into_body_str().contains("max_header_bytes"));
+    }
+}
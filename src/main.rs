@@ -1,49 +1,60 @@
 mod assets;
 mod auth;
+mod client_cert;
+mod client_hello;
+mod compat;
+mod config;
 mod cookies;
+mod cors;
+mod decompress;
+mod device;
 mod dynamic_data;
+mod error;
+mod format;
+mod forwarded;
+mod geo;
 mod http_methods;
 mod images;
+mod inspect;
+mod limits;
+mod middleware;
+mod multipart;
+mod overrides;
 mod redirects;
 mod request_inspection;
 mod response_inspection;
 mod response_formats;
+#[macro_use]
+mod router;
 mod status_codes;
+mod timing;
+mod user_agent;
 mod utils;
 
-use fastly::http::{Method, StatusCode};
-use fastly::{Error, mime, Request, Response};
-use regex_lite::{Regex};
-use utoipa::OpenApi;
+use fastly::http::{header, Method, StatusCode};
+use fastly::{mime, Request, Response};
+use config::CONFIG;
+use error::Error;
+use middleware::{AccessLog, Pipeline, RequestId, ServerTiming};
+use router::{allow_header, Endpoint, Match, Params, Router};
+use std::sync::LazyLock;
+use std::time::Instant;
+use timing::SERVER_TIMING;
+use utoipa::{Modify, OpenApi};
+use utoipa::openapi::Server;
 
 #[derive(OpenApi)]
 #[openapi(
-  paths(
-    auth::bearer, auth::basic_auth,
-    cookies::get_cookies, cookies::set_cookie, cookies::delete_cookie,
-    dynamic_data::uuid, dynamic_data::delay_get, dynamic_data::delay_post, dynamic_data::base64,
-    dynamic_data::bytes,
-    http_methods::delete, http_methods::get, http_methods::put, http_methods::post, http_methods::patch,
-    images::image, images::jpeg, images::png, images::svg, images::webp,
-    redirects::absolute_redirect,
-    redirects::relative_redirect, redirects::redirect,
-    request_inspection::user_agent, request_inspection::ip, request_inspection::headers,
-    request_inspection::http_version_get, request_inspection::http_version_post,
-    request_inspection::http_version_put, request_inspection::http_version_patch,
-    request_inspection::http_version_delete,
-    response_inspection::cache_value,
-    response_inspection::response_headers_get, response_inspection::response_headers_post,
-    response_inspection::etag,
-    response_formats::html, response_formats::json, response_formats::xml, response_formats::encoding_utf8,
-    response_formats::deny, response_formats::robots_txt, response_formats::brotli,
-    response_formats::deflate, response_formats::gzip,
-    status_codes::get, status_codes::post, status_codes::put, status_codes::patch, status_codes::delete,
-  ),
+  modifiers(&EndpointPaths),
   tags(
+    (name = "Anything", description = "Returns anything that is passed to request"),
     (name = "Auth", description = "Auth methods"),
     (name = "Cookies", description = "Cookie manipulation"),
+    (name = "CORS", description = "Returns arbitrary CORS headers"),
+    (name = "Documentation", description = "Describes this API"),
     (name = "Dynamic data", description = "Generates random and dynamic data"),
     (name = "HTTP Methods", description = "Testing different HTTP verbs"),
+    (name = "Images", description = "Returns different image formats"),
     (name = "Redirects", description = "Returns different redirect responses"),
     (name = "Request inspection", description = "Inspect the request data"),
     (name = "Response inspection", description = "Inspect the response data like caching and headers"),
@@ -53,103 +64,358 @@ use utoipa::OpenApi;
 )]
 struct ApiDoc;
 
-enum ReqHandler {
-    MutHandler (fn(&mut Request) -> Result<Response, Error>),
-    Handler(fn(&Request) -> Result<Response, Error>),
+/// Documents every endpoint registered in ENDPOINTS, served under the base path.
+struct EndpointPaths;
+
+impl Modify for EndpointPaths {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for e in ENDPOINTS.iter() {
+            openapi.paths.add_path_operation(&e.path, e.methods.clone(), e.operation.clone());
+        }
+        if !CONFIG.base_path.is_empty() {
+            openapi.servers = Some(vec![Server::new(&CONFIG.base_path)]);
+        }
+    }
 }
 
-fn rr_swagger(_: &Request) -> Result<Response, Error> {
+#[utoipa::path(
+    get,
+    path = "/swagger.json",
+    tag = "Documentation",
+    responses(
+        (status = 200, description = "The OpenAPI document for this API.", content_type = "application/json")
+    )
+)]
+/// Returns this OpenAPI document.
+fn rr_swagger(_: &Request, _: &Params) -> Result<Response, Error> {
     Ok(Response::from_status(StatusCode::OK)
         .with_content_type(mime::APPLICATION_JSON)
         .with_body(ApiDoc::openapi().to_pretty_json().unwrap_or_default()))
 }
 
-fn route(routes:Vec<(Method, Regex, ReqHandler)>, req: &mut Request) -> Result<Response, Error>{
-   for (method, r, handler) in routes {
-       if method == req.get_method() && r.is_match(req.get_path()) {
-           return match handler {
-               ReqHandler::MutHandler(cb) => cb(req),
-               ReqHandler::Handler(cb) => cb(req),
-           };
-       }
-   }
-
-   Ok(Response::from_status(StatusCode::NOT_FOUND)
-     .with_content_type(mime::TEXT_HTML_UTF_8))
+static ENDPOINTS: LazyLock<Vec<Endpoint>> = LazyLock::new(|| endpoints![
+    Handler(self::rr_swagger),
+    MutHandler(http_methods::anything) any,
+    MutHandler(http_methods::anything_path) any,
+    Handler(auth::basic_auth),
+    Handler(auth::bearer),
+    Handler(auth::client_cert),
+    Handler(cookies::get_cookies),
+    Handler(cookies::set_cookie),
+    Handler(cookies::delete_cookie),
+    Handler(cors::get),
+    Handler(cors::post),
+    Handler(cors::options),
+    Handler(device::device),
+    Handler(dynamic_data::base64),
+    Handler(dynamic_data::bytes),
+    Handler(dynamic_data::uuid),
+    Handler(dynamic_data::delay_get),
+    MutHandler(dynamic_data::delay_post),
+    Handler(geo::geo),
+    Handler(http_methods::get),
+    MutHandler(http_methods::post),
+    MutHandler(http_methods::put),
+    MutHandler(http_methods::patch),
+    MutHandler(http_methods::delete),
+    Handler(images::image),
+    Handler(images::jpeg),
+    Handler(images::png),
+    Handler(images::svg),
+    Handler(images::webp),
+    MutHandler(inspect::inspect) any,
+    Handler(redirects::absolute_redirect),
+    Handler(redirects::relative_redirect),
+    Handler(redirects::redirect),
+    Handler(request_inspection::user_agent),
+    Handler(request_inspection::user_agent_parsed),
+    Handler(request_inspection::ip),
+    Handler(request_inspection::headers),
+    Handler(request_inspection::http_version_get),
+    Handler(request_inspection::http_version_post),
+    Handler(request_inspection::http_version_put),
+    Handler(request_inspection::http_version_patch),
+    Handler(request_inspection::http_version_delete),
+    Handler(request_inspection::tls),
+    Handler(response_formats::brotli),
+    Handler(response_formats::deflate),
+    Handler(response_formats::gzip),
+    Handler(response_formats::html),
+    Handler(response_formats::json),
+    Handler(response_formats::robots_txt),
+    Handler(response_formats::xml),
+    Handler(response_formats::deny),
+    Handler(response_formats::encoding_utf8),
+    Handler(response_formats::utf8),
+    Handler(response_inspection::cache_value),
+    Handler(response_inspection::etag),
+    Handler(response_inspection::response_headers_get),
+    Handler(response_inspection::response_headers_post),
+    Handler(status_codes::get),
+    MutHandler(status_codes::post),
+    MutHandler(status_codes::put),
+    MutHandler(status_codes::patch),
+    Handler(status_codes::delete),
+]);
+
+static ROUTER: LazyLock<Router> = LazyLock::new(|| Router::from_endpoints(&ENDPOINTS));
+
+fn route(req: &mut Request, path: &str) -> Response {
+    let method = req.get_method().clone();
+    let start = Instant::now();
+    let found = ROUTER.find(&method, path);
+    let routing = start.elapsed();
+
+    let start = Instant::now();
+    let resp = match found {
+        Match::Found(handler, params) => handler.call(req, &params),
+        Match::Options(allowed) => Ok(Response::from_status(StatusCode::NO_CONTENT)
+            .with_header(header::ALLOW, allow_header(&allowed))),
+        Match::MethodNotAllowed(allowed) => Err(Error::MethodNotAllowed(allowed)),
+        Match::NotFound => Err(Error::NotFound(format!("{} was not found", path))),
+    };
+    let mut resp = resp.unwrap_or_else(Error::into_response);
+    resp.append_header(SERVER_TIMING, timing::metric("route", routing));
+    resp.append_header(SERVER_TIMING, timing::metric("handler", start.elapsed()));
+
+    // HEAD is served by the GET handler, with the body dropped
+    if method == Method::HEAD {
+        resp.take_body();
+    }
+    resp
 }
 
-#[fastly::main]
-fn main(mut req: Request) -> Result<Response, Error> {
+/// Serves the request from the swagger-ui assets or the router.
+fn serve(req: &mut Request) -> Response {
+    let Some(path) = CONFIG.strip_base_path(req.get_path()).map(String::from) else {
+        return Error::NotFound(format!("{} is outside of {}", req.get_path(), CONFIG.base_path)).into_response();
+    };
+
     // Aliases to index.html
-    let path = match req.get_path() {
+    let asset_path = match path.as_str() {
         "/" | "/index" | "/index.html" => "/index.html",
-        _ => req.get_path(),
+        _ => path.as_str(),
     };
 
     // The URL matches a file from the swagger-ui folder - we return it
-    if let Some(asset) = assets::Asset::get(format!("swagger-ui{path}").as_str()) {
-        return Ok(Response::from_status(StatusCode::OK)
-            .with_body_octet_stream(asset.data.as_ref())
-            .with_content_type(assets::file_mimetype(path, mime::APPLICATION_OCTET_STREAM)));
-    }
-
-    use ReqHandler::*;
-    let routes = vec![
-        (Method::GET, Regex::new(r"/swagger\.json$")?, Handler(rr_swagger)),
-        (Method::GET, Regex::new(r"^/cookies$")?, Handler(cookies::get_cookies)),
-        (Method::GET, Regex::new(r"^/cookies/set/([^/]+)/([^/]+)$")?, Handler(cookies::set_cookie)),
-        (Method::GET, Regex::new(r"^/cookies/delete/([^/]+)$")?, Handler(cookies::delete_cookie)),
-        (Method::GET, Regex::new(r"^/status/((\d{3},?)+)$")?, Handler(status_codes::get)),
-        (Method::POST, Regex::new(r"^/status/(\d{3})$")?, MutHandler(status_codes::post)),
-        (Method::PUT, Regex::new(r"^/status/(\d{3})$")?, MutHandler(status_codes::put)),
-        (Method::PATCH, Regex::new(r"^/status/(\d{3})$")?, MutHandler(status_codes::patch)),
-        (Method::DELETE, Regex::new(r"^/status/(\d{3})$")?, Handler(status_codes::delete)),
-        (Method::PATCH, Regex::new(r"^/patch$")?, MutHandler(http_methods::patch)),
-        (Method::POST, Regex::new(r"^/post$")?, MutHandler(http_methods::post)),
-        (Method::PUT, Regex::new(r"^/put$")?, MutHandler(http_methods::put)),
-        (Method::DELETE, Regex::new(r"^/delete$")?, Handler(http_methods::delete)),
-        (Method::GET, Regex::new(r"^/get$")?, Handler(http_methods::get)),
-        (Method::GET, Regex::new(r"^/image$")?, Handler(images::image)),
-        (Method::GET, Regex::new(r"^/image/jpeg$")?, Handler(images::jpeg)),
-        (Method::GET, Regex::new(r"^/image/png$")?, Handler(images::png)),
-        (Method::GET, Regex::new(r"^/image/svg$")?, Handler(images::svg)),
-        (Method::GET, Regex::new(r"^/image/webp$")?, Handler(images::webp)),
-        (Method::GET, Regex::new(r"^/deflate$")?, Handler(response_formats::deflate)),
-        (Method::GET, Regex::new(r"^/brotli$")?, Handler(response_formats::brotli)),
-        (Method::GET, Regex::new(r"^/gzip$")?, Handler(response_formats::gzip)),
-        (Method::GET, Regex::new(r"^/html$")?, Handler(response_formats::html)),
-        (Method::GET, Regex::new(r"^/json$")?, Handler(response_formats::json)),
-        (Method::GET, Regex::new(r"^/robots\.txt$")?, Handler(response_formats::robots_txt)),
-        (Method::GET, Regex::new(r"^/xml$")?, Handler(response_formats::xml)),
-        (Method::GET, Regex::new(r"^/deny$")?, Handler(response_formats::deny)),
-        (Method::GET, Regex::new(r"^/utf8$")?, Handler(response_formats::encoding_utf8)),
-        (Method::GET, Regex::new(r"^/user-agent$")?, Handler(request_inspection::user_agent)),
-        (Method::GET, Regex::new(r"^/ip$")?, Handler(request_inspection::ip)),
-        (Method::GET, Regex::new(r"^/http-version$")?, Handler(request_inspection::http_version_get)),
-        (Method::POST, Regex::new(r"^/http-version$")?, Handler(request_inspection::http_version_post)),
-        (Method::PUT, Regex::new(r"^/http-version$")?, Handler(request_inspection::http_version_put)),
-        (Method::PATCH, Regex::new(r"^/http-version$")?, Handler(request_inspection::http_version_patch)),
-        (Method::DELETE, Regex::new(r"^/http-version$")?, Handler(request_inspection::http_version_delete)),
-        (Method::GET, Regex::new(r"^/basic-auth/(\w+)/(\w+)$")?, Handler(auth::basic_auth)),
-        (Method::GET, Regex::new(r"^/bearer$")?, Handler(auth::bearer)),
-        (Method::GET, Regex::new(r"^/base64/([A-Za-z0-9+/=]{1,4096})$")?, Handler(dynamic_data::base64)),
-        (Method::GET, Regex::new(r"^/bytes/(\d{1,5})$")?, Handler(dynamic_data::bytes)),
-        (Method::GET, Regex::new(r"^/uuid$")?, Handler(dynamic_data::uuid)),
-        (Method::GET, Regex::new(r"^/delay/(\d{1,2})$")?, Handler(dynamic_data::delay_get)),
-        (Method::POST, Regex::new(r"^/delay/(\d{1,2})$")?, MutHandler(dynamic_data::delay_post)),
-        (Method::GET, Regex::new(r"^/headers$")?, Handler(request_inspection::headers)),
-        (Method::GET, Regex::new(r"^/etag/(\w+)$")?, Handler(response_inspection::etag)),
-        (Method::GET, Regex::new(r"^/cache/(\d{1,2})$")?, Handler(response_inspection::cache_value)),
-        (Method::GET, Regex::new(r"^/response-headers$")?, Handler(response_inspection::response_headers_get)),
-        (Method::POST, Regex::new(r"^/response-headers$")?, Handler(response_inspection::response_headers_post)),
-        (Method::GET, Regex::new(r"^/absolute-redirect/(\d{1})$")?, Handler(redirects::absolute_redirect)),
-        (Method::GET, Regex::new(r"^/relative-redirect/(\d{1})$")?, Handler(redirects::relative_redirect)),
-        (Method::GET, Regex::new(r"^/redirect/(\d{1})$")?, Handler(redirects::redirect)),
-    ];
-
-    route(routes, &mut req).map (|resp|
-        resp
-            .with_header("access-control-allow-origin", "*")
-            .with_header("access-control-allow-credentials", "true")
-    )
+    if let Some(asset) = assets::serve_swagger_ui(asset_path, &CONFIG.base_path) {
+        return asset;
+    }
+
+    route(req, &path)
+}
+
+/// Middlewares wrapped around `serve`. The first one sees the request first
+/// and the response last, so the total time covers the whole chain. Limits
+/// come after the request ID, access log and CORS, so that requests they
+/// reject still get all three, and before response overrides, so that no
+/// override can rewrite their rejections.
+fn pipeline() -> Pipeline {
+    Pipeline::new(vec![
+        Box::new(ServerTiming::default()),
+        Box::new(RequestId::default()),
+        Box::new(AccessLog::default()),
+        Box::new(CONFIG.cors.clone()),
+        Box::new(CONFIG.limits.clone()),
+        Box::new(overrides::ResponseOverrides::default()),
+    ])
+}
+
+#[fastly::main]
+fn main(mut req: Request) -> Result<Response, fastly::Error> {
+    Ok(pipeline().run(&mut req, serve))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_method_not_allowed() {
+        let mut req = Request::from_client()
+            .with_method(Method::PUT)
+            .with_path("/get");
+        let resp = route(&mut req, "/get");
+        assert_eq!(resp.get_status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.get_header_str("allow"), Some("GET, HEAD, OPTIONS"));
+        assert_eq!(resp.get_header_str("content-type"), Some(error::APPLICATION_PROBLEM_JSON));
+    }
+
+    #[test]
+    fn test_anything_any_method() {
+        let mut req = Request::from_client()
+            .with_method(Method::from_bytes(b"QUERY").unwrap())
+            .with_path("/anything/a/b/c");
+        let resp = route(&mut req, "/anything/a/b/c");
+        assert_eq!(resp.get_status(), StatusCode::OK);
+        assert_eq!(resp.get_content_type(), Some(mime::APPLICATION_JSON));
+    }
+
+    /// Substitutes every `{param}` in a documented path with a value.
+    fn concrete_path(path: &str) -> String {
+        path.split('/')
+            .map(|s| if s.starts_with('{') { "1" } else { s })
+            .collect::<Vec<&str>>()
+            .join("/")
+    }
+
+    #[test]
+    fn test_documented_paths_are_routed() {
+        let doc = ApiDoc::openapi();
+        for (path, item) in doc.paths.paths.iter() {
+            let operations = [
+                (Method::GET, &item.get), (Method::POST, &item.post), (Method::PUT, &item.put),
+                (Method::PATCH, &item.patch), (Method::DELETE, &item.delete),
+                (Method::OPTIONS, &item.options), (Method::HEAD, &item.head), (Method::TRACE, &item.trace),
+            ];
+            for (method, _) in operations.iter().filter(|(_, op)| op.is_some()) {
+                assert!(matches!(ROUTER.find(method, &concrete_path(path)), Match::Found(..)),
+                    "{} {} is documented but not routed", method, path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_routed_paths_are_documented() {
+        let doc = ApiDoc::openapi();
+        for (method, pattern) in ROUTER.patterns() {
+            let item = doc.paths.paths.get(&pattern);
+            assert!(item.is_some(), "{} is routed but not documented", pattern);
+            let item = item.unwrap();
+            let documented = match method {
+                None => item.get.is_some(),
+                Some(Method::GET) => item.get.is_some(),
+                Some(Method::POST) => item.post.is_some(),
+                Some(Method::PUT) => item.put.is_some(),
+                Some(Method::PATCH) => item.patch.is_some(),
+                Some(Method::DELETE) => item.delete.is_some(),
+                Some(Method::OPTIONS) => item.options.is_some(),
+                Some(_) => false,
+            };
+            assert!(documented, "{:?} {} is routed but not documented", method, pattern);
+        }
+    }
+
+    #[test]
+    fn test_server_timing() {
+        let mut req = Request::from_client()
+            .with_path("/get");
+        let resp = route(&mut req, "/get");
+        let timings = resp.get_header_all_str(SERVER_TIMING);
+        assert_eq!(timings.len(), 2);
+        assert!(timings[0].starts_with("route;dur="));
+        assert!(timings[1].starts_with("handler;dur="));
+    }
+
+    #[test]
+    fn test_not_found() {
+        let mut req = Request::from_client()
+            .with_path("/does-not-exist");
+        let resp = route(&mut req, "/does-not-exist");
+        assert_eq!(resp.get_status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.get_header_str("content-type"), Some(error::APPLICATION_PROBLEM_JSON));
+    }
+
+    #[test]
+    fn test_utf8() {
+        for path in ["/utf8", "/encoding/utf8"].iter() {
+            let mut req = Request::from_client()
+                .with_path(path);
+            let resp = route(&mut req, path);
+            assert_eq!(resp.get_status(), StatusCode::OK);
+            assert_eq!(resp.get_content_type(), Some(mime::TEXT_PLAIN));
+        }
+    }
+
+    #[test]
+    fn test_head() {
+        let mut req = Request::from_client()
+            .with_method(Method::HEAD)
+            .with_path("/json");
+        let mut resp = route(&mut req, "/json");
+        assert_eq!(resp.get_status(), StatusCode::OK);
+        assert_eq!(resp.get_content_type(), Some(mime::APPLICATION_JSON));
+        assert!(resp.take_body_bytes().is_empty());
+    }
+
+    #[test]
+    fn test_cors_preflight() {
+        let mut req = Request::from_client()
+            .with_method(Method::OPTIONS)
+            .with_header("origin", "https://example.org")
+            .with_header("access-control-request-method", "POST")
+            .with_path("/post");
+        let resp = route(&mut req, "/post");
+        let resp = cors::CorsPolicy::default().apply(&req, resp);
+        assert_eq!(resp.get_status(), StatusCode::NO_CONTENT);
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("https://example.org"));
+        assert_eq!(resp.get_header_str("access-control-allow-methods"), Some("POST"));
+    }
+
+    #[test]
+    fn test_pipeline_order() {
+        assert_eq!(pipeline().names(), vec!["server-timing", "request-id", "access-log", "cors", "limits", "response-overrides"]);
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let mut req = Request::from_client()
+            .with_header("origin", "https://example.org")
+            .with_path("/get");
+        let resp = pipeline().run(&mut req, serve);
+        assert_eq!(resp.get_status(), StatusCode::OK);
+        assert!(resp.contains_header(utils::X_REQUEST_ID));
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("https://example.org"));
+        assert!(resp.get_header_all_str(SERVER_TIMING).last().unwrap().starts_with("total;dur="));
+
+        let mut req = Request::from_client()
+            .with_header("origin", "https://example.org")
+            .with_path("/cors");
+        let resp = pipeline().run(&mut req, serve);
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), None);
+    }
+
+    #[test]
+    fn test_pipeline_limits_before_overrides() {
+        let query = format!("_status=200&foo={}", "a".repeat(CONFIG.limits.max_url_bytes));
+        let mut req = Request::from_client()
+            .with_path("/get")
+            .with_query_str(query);
+        let resp = pipeline().run(&mut req, serve);
+        assert_eq!(resp.get_status(), StatusCode::URI_TOO_LONG);
+    }
+
+    #[test]
+    fn test_pipeline_limits_rejection_headers() {
+        let mut req = Request::from_client()
+            .with_method(Method::POST)
+            .with_header("origin", "https://example.org")
+            .with_header("content-length", (CONFIG.limits.max_body_bytes + 1).to_string())
+            .with_path("/post");
+        let resp = pipeline().run(&mut req, serve);
+        assert_eq!(resp.get_status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(resp.contains_header(utils::X_REQUEST_ID));
+        assert_eq!(resp.get_header_str("access-control-allow-origin"), Some("https://example.org"));
+    }
+
+    #[test]
+    fn test_pipeline_overrides_win_over_cors() {
+        let mut req = Request::from_client()
+            .with_header("origin", "https://example.org")
+            .with_path("/get")
+            .with_query_str("_header[Access-Control-Allow-Origin]=https://other.example");
+        let resp = pipeline().run(&mut req, serve);
+        assert_eq!(resp.get_header_all_str("access-control-allow-origin"), vec!["https://other.example"]);
+        assert_eq!(resp.get_header_str("access-control-allow-credentials"), Some("true"));
+    }
+
+    #[test]
+    fn test_options() {
+        let mut req = Request::from_client()
+            .with_method(Method::OPTIONS)
+            .with_path("/http-version");
+        let resp = route(&mut req, "/http-version");
+        assert_eq!(resp.get_status(), StatusCode::NO_CONTENT);
+        assert_eq!(resp.get_header_str("allow"), Some("GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS"));
+    }
 }
@@ -0,0 +1,239 @@
+use fastly::{Request, Response};
+use std::cell::RefCell;
+use std::time::Instant;
+use uuid::Uuid;
+use crate::timing::{metric, SERVER_TIMING};
+use crate::utils::X_REQUEST_ID;
+
+/// A cross-cutting behaviour wrapped around every request.
+pub trait Middleware {
+    /// Identifies the middleware when checking the pipeline's order.
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn name(&self) -> &'static str;
+
+    /// Called before the request is served, in pipeline order. Returning a
+    /// response short-circuits the request: the handler and the following
+    /// middlewares are skipped.
+    fn before(&mut self, _req: &mut Request) -> Option<Response> {
+        None
+    }
+
+    /// Called after the request was served, in reverse pipeline order.
+    fn after(&mut self, _req: &Request, resp: Response) -> Response {
+        resp
+    }
+}
+
+/// An ordered chain of middlewares. The first middleware sees the request
+/// first and the response last.
+pub struct Pipeline {
+    middlewares: Vec<Box<dyn Middleware>>,
+}
+
+impl Pipeline {
+    pub fn new(middlewares: Vec<Box<dyn Middleware>>) -> Self {
+        Pipeline { middlewares }
+    }
+
+    #[cfg(test)]
+    pub fn names(&self) -> Vec<&'static str> {
+        self.middlewares.iter().map(|m| m.name()).collect()
+    }
+
+    pub fn run(&mut self, req: &mut Request, serve: impl FnOnce(&mut Request) -> Response) -> Response {
+        let mut resp = None;
+        let mut ran = 0;
+        for m in self.middlewares.iter_mut() {
+            resp = m.before(req);
+            if resp.is_some() {
+                break;
+            }
+            ran += 1;
+        }
+
+        let resp = resp.unwrap_or_else(|| serve(req));
+        self.middlewares[..ran]
+            .iter_mut()
+            .rev()
+            .fold(resp, |resp, m| m.after(req, resp))
+    }
+}
+
+thread_local! {
+    /// The id of the request being served. Compute serves one request per
+    /// instance, so this is scoped to the request.
+    static REQUEST_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// The id RequestId gave the request being served, None outside of the pipeline.
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.with(|id| id.borrow().clone())
+}
+
+/// Echoes the client's X-Request-Id, or makes one up. The request itself is
+/// left as the client sent it: echo endpoints read the id from `request_id`.
+#[derive(Default)]
+pub struct RequestId {
+    id: String,
+}
+
+impl Middleware for RequestId {
+    fn name(&self) -> &'static str {
+        "request-id"
+    }
+
+    fn before(&mut self, req: &mut Request) -> Option<Response> {
+        self.id = req.get_header(X_REQUEST_ID)
+            .and_then(|id| id.to_str().ok())
+            .filter(|id| !id.is_empty())
+            .map(String::from)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        REQUEST_ID.with(|id| *id.borrow_mut() = Some(self.id.clone()));
+        None
+    }
+
+    fn after(&mut self, _: &Request, mut resp: Response) -> Response {
+        REQUEST_ID.with(|id| id.borrow_mut().take());
+        resp.set_header(X_REQUEST_ID, &self.id);
+        resp
+    }
+}
+
+/// Adds the total time spent on the request to Server-Timing.
+pub struct ServerTiming {
+    start: Instant,
+}
+
+impl Default for ServerTiming {
+    fn default() -> Self {
+        ServerTiming { start: Instant::now() }
+    }
+}
+
+impl Middleware for ServerTiming {
+    fn name(&self) -> &'static str {
+        "server-timing"
+    }
+
+    fn before(&mut self, _: &mut Request) -> Option<Response> {
+        self.start = Instant::now();
+        None
+    }
+
+    fn after(&mut self, _: &Request, mut resp: Response) -> Response {
+        resp.append_header(SERVER_TIMING, metric("total", self.start.elapsed()));
+        resp
+    }
+}
+
+/// Writes one line per request to stdout, which Compute forwards to log tailing.
+#[derive(Default)]
+pub struct AccessLog {
+    method: String,
+    url: String,
+}
+
+impl Middleware for AccessLog {
+    fn name(&self) -> &'static str {
+        "access-log"
+    }
+
+    fn before(&mut self, req: &mut Request) -> Option<Response> {
+        self.method = req.get_method_str().to_string();
+        self.url = req.get_url_str().to_string();
+        None
+    }
+
+    fn after(&mut self, _: &Request, resp: Response) -> Response {
+        println!("{} {} {} {}",
+                 self.method, self.url, resp.get_status().as_u16(),
+                 request_id().as_deref().unwrap_or("-"));
+        resp
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastly::http::{HeaderValue, StatusCode};
+
+    /// Appends its name to the x-trace header of the request and the response.
+    struct Trace(&'static str, bool);
+
+    impl Middleware for Trace {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn before(&mut self, req: &mut Request) -> Option<Response> {
+            req.append_header("x-trace", self.0);
+            if self.1 {
+                return Some(Response::from_status(StatusCode::PAYLOAD_TOO_LARGE));
+            }
+            None
+        }
+
+        fn after(&mut self, _: &Request, resp: Response) -> Response {
+            resp.with_header("x-trace", self.0)
+        }
+    }
+
+    fn serve(req: &mut Request) -> Response {
+        Response::from_status(StatusCode::OK)
+            .with_header("x-served", req.get_header_all_str("x-trace").join(","))
+    }
+
+    #[test]
+    fn test_order() {
+        let mut pipeline = Pipeline::new(vec![
+            Box::new(Trace("a", false)),
+            Box::new(Trace("b", false)),
+        ]);
+        assert_eq!(pipeline.names(), vec!["a", "b"]);
+
+        let mut req = Request::from_client();
+        let resp = pipeline.run(&mut req, serve);
+        assert_eq!(resp.get_status(), StatusCode::OK);
+        assert_eq!(resp.get_header_str("x-served"), Some("a,b"));
+        assert_eq!(resp.get_header_all_str("x-trace"), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let mut pipeline = Pipeline::new(vec![
+            Box::new(Trace("a", false)),
+            Box::new(Trace("b", true)),
+            Box::new(Trace("c", false)),
+        ]);
+
+        let mut req = Request::from_client();
+        let resp = pipeline.run(&mut req, serve);
+        assert_eq!(resp.get_status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(req.get_header_all_str("x-trace"), vec!["a", "b"]);
+        assert_eq!(resp.get_header_all_str("x-trace"), vec!["a"]);
+    }
+
+    #[test]
+    fn test_request_id() {
+        let mut pipeline = Pipeline::new(vec![Box::new(RequestId::default())]);
+
+        let mut req = Request::from_client()
+            .with_header(X_REQUEST_ID, "foo");
+        let resp = pipeline.run(&mut req, serve);
+        assert_eq!(resp.get_header_str(X_REQUEST_ID), Some("foo"));
+
+        let mut req = Request::from_client();
+        let resp = pipeline.run(&mut req, |_| Response::from_status(StatusCode::OK)
+            .with_header("x-served", request_id().unwrap_or_default()));
+        let id = resp.get_header_str(X_REQUEST_ID).unwrap();
+        assert!(Uuid::parse_str(id).is_ok());
+        assert_eq!(resp.get_header_str("x-served"), Some(id));
+        assert!(!req.contains_header(X_REQUEST_ID));
+        assert_eq!(request_id(), None);
+
+        let mut req = Request::from_client()
+            .with_header(X_REQUEST_ID, HeaderValue::from_bytes(b"caf\xe9").unwrap());
+        let resp = pipeline.run(&mut req, serve);
+        assert!(Uuid::parse_str(resp.get_header_str(X_REQUEST_ID).unwrap()).is_ok());
+    }
+}
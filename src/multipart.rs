@@ -0,0 +1,145 @@
+use crate::error::Error;
+
+/// A part of a `multipart/form-data` body.
+#[derive(Debug, PartialEq)]
+pub struct Part {
+    pub name: String,
+    /// Set for file uploads.
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Splits a header parameter list such as `form-data; name="a;b"; filename=c.txt`
+/// on semicolons outside of quoted strings, and unquotes the values.
+fn parameters(value: &str) -> Vec<(String, String)> {
+    let mut params = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in value.chars().chain(std::iter::once(';')) {
+        match c {
+            _ if escaped => {
+                current.push(c);
+                escaped = false;
+            },
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                if let Some((k, v)) = current.split_once('=') {
+                    params.push((k.trim().to_ascii_lowercase(), v.trim().to_string()));
+                }
+                current.clear();
+            },
+            _ => current.push(c),
+        }
+    }
+    params
+}
+
+fn parse_part(part: &[u8]) -> Result<Part, Error> {
+    let invalid = |detail: &str| Error::BadRequest(format!("Invalid multipart body: {}", detail));
+    let end = find(part, b"\r\n\r\n").ok_or_else(|| invalid("part without headers"))?;
+    let headers = String::from_utf8_lossy(&part[..end]);
+
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+    for line in headers.split("\r\n") {
+        let Some((k, v)) = line.split_once(':') else { continue };
+        match k.trim().to_ascii_lowercase().as_str() {
+            "content-disposition" => for (k, v) in parameters(v) {
+                match k.as_str() {
+                    "name" => name = Some(v),
+                    "filename" => filename = Some(v),
+                    _ => {},
+                }
+            },
+            "content-type" => content_type = Some(v.trim().to_string()),
+            _ => {},
+        }
+    }
+
+    Ok(Part {
+        name: name.ok_or_else(|| invalid("part without a name"))?,
+        filename,
+        content_type,
+        body: part[end + 4..].to_vec(),
+    })
+}
+
+/// Parses a `multipart/form-data` body (RFC 7578) delimited by `boundary`.
+pub fn parse(body: &[u8], boundary: &str) -> Result<Vec<Part>, Error> {
+    let invalid = |detail: &str| Error::BadRequest(format!("Invalid multipart body: {}", detail));
+    let delimiter = format!("--{}", boundary);
+    let start = find(body, delimiter.as_bytes()).ok_or_else(|| invalid("boundary not found"))?;
+
+    // Every part is followed by CRLF and the delimiter, the last one by `--`
+    let delimiter = format!("\r\n--{}", boundary);
+    let mut rest = &body[start + delimiter.len() - 2..];
+    let mut parts = vec![];
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+        rest = rest.strip_prefix(b"\r\n").ok_or_else(|| invalid("missing line break after boundary"))?;
+        let end = find(rest, delimiter.as_bytes()).ok_or_else(|| invalid("missing closing boundary"))?;
+        parts.push(parse_part(&rest[..end])?);
+        rest = &rest[end + delimiter.len()..];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BODY: &str = "preamble\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"comment\"\r\n\
+        \r\n\
+        hello\r\nworld\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"upload\"; filename=\"a;b.txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        file content\r\n\
+        --XyZ--\r\n";
+
+    #[test]
+    fn test_parse() {
+        let parts = parse(BODY.as_bytes(), "XyZ").unwrap();
+        assert_eq!(parts, vec![
+            Part {
+                name: String::from("comment"),
+                filename: None,
+                content_type: None,
+                body: b"hello\r\nworld".to_vec(),
+            },
+            Part {
+                name: String::from("upload"),
+                filename: Some(String::from("a;b.txt")),
+                content_type: Some(String::from("text/plain")),
+                body: b"file content".to_vec(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse(BODY.as_bytes(), "other").is_err());
+        assert!(parse(b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nno end", "XyZ").is_err());
+        assert!(parse(b"--XyZ\r\nContent-Type: text/plain\r\n\r\nx\r\n--XyZ--", "XyZ").is_err());
+    }
+
+    #[test]
+    fn test_parameters() {
+        assert_eq!(parameters(r#" form-data; name="a\"b"; filename=c.txt"#), vec![
+            (String::from("name"), String::from("a\"b")),
+            (String::from("filename"), String::from("c.txt")),
+        ]);
+    }
+}
@@ -0,0 +1,162 @@
+use fastly::http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use fastly::http::StatusCode;
+use fastly::{Request, Response};
+use crate::error::Error;
+use crate::middleware::Middleware;
+use std::{thread, time};
+
+/// Longest delay `_delay_ms` may ask for, the same as /delay.
+const MAX_DELAY_MS: u64 = 10_000;
+
+/// Rewrites the response of any endpoint from reserved query parameters:
+/// `_status`, `_delay_ms`, `_header[Name]=value` and `_content_type`.
+/// The parameters are removed from the request, so handlers never see them.
+#[derive(Debug, Default, PartialEq)]
+pub struct ResponseOverrides {
+    status: Option<StatusCode>,
+    delay: Option<time::Duration>,
+    content_type: Option<HeaderValue>,
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl ResponseOverrides {
+    /// Parses the reserved parameters out of the query string, returning the
+    /// overrides and the remaining parameters.
+    fn parse(arg_pairs: Vec<(String, String)>) -> Result<(Self, Vec<(String, String)>), Error> {
+        let mut overrides = ResponseOverrides::default();
+        let mut rest = vec![];
+
+        for (k, v) in arg_pairs {
+            match k.as_str() {
+                "_status" => {
+                    let status = v.parse::<u16>().ok()
+                        .and_then(|s| StatusCode::from_u16(s).ok())
+                        .ok_or_else(|| Error::BadRequest(format!("Invalid _status: {}", v)))?;
+                    overrides.status = Some(status);
+                },
+                "_delay_ms" => {
+                    let ms = v.parse::<u64>()
+                        .map_err(|_| Error::BadRequest(format!("Invalid _delay_ms: {}", v)))?;
+                    overrides.delay = Some(time::Duration::from_millis(ms.min(MAX_DELAY_MS)));
+                },
+                "_content_type" => {
+                    let content_type = HeaderValue::from_str(&v)
+                        .map_err(|_| Error::BadRequest(format!("Invalid _content_type: {}", v)))?;
+                    overrides.content_type = Some(content_type);
+                },
+                _ => match k.strip_prefix("_header[").and_then(|k| k.strip_suffix(']')) {
+                    Some(name) => {
+                        let header = HeaderName::from_bytes(name.as_bytes()).ok()
+                            .zip(HeaderValue::from_str(&v).ok())
+                            .ok_or_else(|| Error::BadRequest(format!("Invalid header override: {}={}", name, v)))?;
+                        overrides.headers.push(header);
+                    },
+                    None => rest.push((k, v)),
+                },
+            }
+        }
+
+        Ok((overrides, rest))
+    }
+}
+
+impl Middleware for ResponseOverrides {
+    fn name(&self) -> &'static str {
+        "response-overrides"
+    }
+
+    fn before(&mut self, req: &mut Request) -> Option<Response> {
+        // Malformed query strings are left for the handlers to report
+        let Ok(arg_pairs) = req.get_query::<Vec<(String, String)>>() else {
+            return None;
+        };
+        if !arg_pairs.iter().any(|(k, _)| k.starts_with('_')) {
+            return None;
+        }
+
+        match ResponseOverrides::parse(arg_pairs) {
+            Ok((overrides, rest)) => {
+                *self = overrides;
+                if rest.is_empty() {
+                    req.remove_query();
+                } else if let Err(e) = req.set_query(&rest) {
+                    return Some(Error::Internal(e.to_string()).into_response());
+                }
+                None
+            },
+            Err(e) => Some(e.into_response()),
+        }
+    }
+
+    fn after(&mut self, _: &Request, mut resp: Response) -> Response {
+        if let Some(status) = self.status {
+            resp.set_status(status);
+        }
+        if let Some(content_type) = &self.content_type {
+            resp.set_header(CONTENT_TYPE, content_type);
+        }
+        for (name, value) in &self.headers {
+            resp.set_header(name, value);
+        }
+        if let Some(delay) = self.delay {
+            thread::sleep(delay);
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middleware::Pipeline;
+
+    fn pairs(query: &[(&str, &str)]) -> Vec<(String, String)> {
+        query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let (overrides, rest) = ResponseOverrides::parse(pairs(&[
+            ("_status", "503"), ("foo", "bar"), ("_delay_ms", "20000"),
+            ("_header[X-Foo]", "baz"), ("_content_type", "text/plain"),
+        ])).unwrap();
+        assert_eq!(overrides.status, Some(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(overrides.delay, Some(time::Duration::from_millis(MAX_DELAY_MS)));
+        assert_eq!(overrides.content_type, Some(HeaderValue::from_static("text/plain")));
+        assert_eq!(overrides.headers, vec![(HeaderName::from_static("x-foo"), HeaderValue::from_static("baz"))]);
+        assert_eq!(rest, pairs(&[("foo", "bar")]));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(ResponseOverrides::parse(pairs(&[("_status", "42")])).is_err());
+        assert!(ResponseOverrides::parse(pairs(&[("_delay_ms", "soon")])).is_err());
+        assert!(ResponseOverrides::parse(pairs(&[("_header[X Foo]", "bar")])).is_err());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut pipeline = Pipeline::new(vec![Box::new(ResponseOverrides::default())]);
+        let mut req = Request::from_client()
+            .with_path("/json")
+            .with_query_str("_status=503&_header[Retry-After]=2&_content_type=text/plain&foo=bar");
+        let resp = pipeline.run(&mut req, |req| {
+            Response::from_status(StatusCode::OK)
+                .with_body(req.get_query_str().unwrap_or_default().to_string())
+        });
+        assert_eq!(resp.get_status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.get_header_str("retry-after"), Some("2"));
+        assert_eq!(resp.get_header_str("content-type"), Some("text/plain"));
+        assert_eq!(resp.into_body_str(), "foo=bar");
+    }
+
+    #[test]
+    fn test_invalid_override() {
+        let mut pipeline = Pipeline::new(vec![Box::new(ResponseOverrides::default())]);
+        let mut req = Request::from_client()
+            .with_path("/json")
+            .with_query_str("_status=abc");
+        let resp = pipeline.run(&mut req, |_| Response::from_status(StatusCode::OK));
+        assert_eq!(resp.get_status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,6 +1,15 @@
 use fastly::http::StatusCode;
-use fastly::{Error, mime, Request, Response};
-use regex_lite::Regex;
+use fastly::{mime, Request, Response};
+use crate::error::Error;
+use crate::config::CONFIG;
+use crate::router::Params;
+
+/// Number of redirects requested, a single digit.
+fn redirect_count(params: &Params) -> Result<u16, Error> {
+    params.parse::<u16>("n")
+        .filter(|n| *n <= 9)
+        .ok_or_else(|| Error::NotFound(String::from("Number of redirects must be between 0 and 9")))
+}
 
 #[utoipa::path(
     get,
@@ -14,31 +23,22 @@ use regex_lite::Regex;
     )
 )]
 // Absolutely 302 redirects n times.
-pub fn absolute_redirect(req: &Request) -> Result<Response, Error> {
-    let caps = Regex::new(r"/absolute-redirect/(\d{1})$")?
-        .captures(req.get_path());
+pub fn absolute_redirect(req: &Request, params: &Params) -> Result<Response, Error> {
+    let n = redirect_count(params)?;
+    let url = req.get_url();
+    let base_url = format!("{}://{}{}", url.scheme(), url.host_str().unwrap_or_default(), CONFIG.base_path);
 
-    if let Some(caps) = caps {
-        let n = caps.get(1).map_or(404, |m| m.as_str().parse::<u16>().unwrap_or(404));
-        let url = req.get_url();
-        let base_url = format!("{}://{}", url.scheme(), url.host_str().unwrap_or_default());
+    let redirect_to = {
+        if n > 1 {
+            format!("{}/absolute-redirect/{}", base_url, n-1)
+        } else {
+            format!("{}/get", base_url)
+        }
+    };
 
-        let redirect_to = {
-            if n > 1 {
-                format!("{}/absolute-redirect/{}", base_url, n-1)
-            } else {
-                format!("{}/get", base_url)
-            }
-        };
-
-        // Return a 302 redirect with an absolute url
-        return Ok(Response::from_status(StatusCode::FOUND)
-            .with_header("location", redirect_to)
-            .with_content_type(mime::TEXT_HTML_UTF_8));
-    }
-
-    // If the regex doesn't match, return a 404
-    Ok(Response::from_status(StatusCode::NOT_FOUND)
+    // Return a 302 redirect with an absolute url
+    Ok(Response::from_status(StatusCode::FOUND)
+        .with_header("location", redirect_to)
         .with_content_type(mime::TEXT_HTML_UTF_8))
 }
 
@@ -54,23 +54,18 @@ pub fn absolute_redirect(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Relatively 302 redirects n times.
-pub fn relative_redirect(req: &Request) -> Result<Response, Error> {
-    let caps = Regex::new(r"/(?:relative-)?redirect/(\d{1})$")?
-        .captures(req.get_path());
-    if let Some(caps) = caps {
-        let n = caps.get(1).map_or(404, |m| m.as_str().parse::<u16>().unwrap_or(404));
-        let redirect_to = {
-            if n > 1 {
-                format!("/relative-redirect/{}", n-1)
-            } else {
-                String::from("/get")
-            }
-        };
-        return Ok(Response::from_status(StatusCode::FOUND)
-            .with_header("location", redirect_to)
-            .with_content_type(mime::TEXT_HTML_UTF_8));
-    }
-    Ok(Response::from_status(StatusCode::NOT_FOUND)
+pub fn relative_redirect(_: &Request, params: &Params) -> Result<Response, Error> {
+    let n = redirect_count(params)?;
+    let redirect_to = {
+        if n > 1 {
+            format!("{}/relative-redirect/{}", CONFIG.base_path, n-1)
+        } else {
+            format!("{}/get", CONFIG.base_path)
+        }
+    };
+
+    Ok(Response::from_status(StatusCode::FOUND)
+        .with_header("location", redirect_to)
         .with_content_type(mime::TEXT_HTML_UTF_8))
 }
 
@@ -86,8 +81,8 @@ pub fn relative_redirect(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// 302 redirects n times.
-pub fn redirect(req: &Request) -> Result<Response, Error> {
-    relative_redirect(req)
+pub fn redirect(req: &Request, params: &Params) -> Result<Response, Error> {
+    relative_redirect(req, params)
 }
 
 #[cfg(test)]
@@ -99,7 +94,7 @@ mod test {
     fn test_absolute_redirect_success() {
         let req = &Request::from_client()
             .with_path("/absolute-redirect/4");
-        let resp = absolute_redirect(req);
+        let resp = absolute_redirect(req, &Params::from([("n", "4")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::FOUND);
@@ -111,18 +106,16 @@ mod test {
     fn test_absolute_redirect_too_many_redirects() {
         let req = &Request::from_client()
             .with_path("/absolute-redirect/41");
-        let resp = absolute_redirect(req);
-        assert!(resp.is_ok());
-        let resp = resp.unwrap();
-        assert_eq!(resp.get_status(), StatusCode::NOT_FOUND);
-        assert_eq!(resp.get_content_type(), Some(mime::TEXT_HTML_UTF_8));
+        let resp = absolute_redirect(req, &Params::from([("n", "41")]));
+        assert!(resp.is_err());
+        assert_eq!(resp.unwrap_err().status(), StatusCode::NOT_FOUND);
     }
 
     #[test]
     fn test_relative_redirect_success() {
         let req = &Request::from_client()
             .with_path("/relative-redirect/3");
-        let resp = relative_redirect(req);
+        let resp = relative_redirect(req, &Params::from([("n", "3")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::FOUND);
@@ -134,18 +127,16 @@ mod test {
     fn test_relative_redirect_too_many_redirects() {
         let req = &Request::from_client()
             .with_path("/relative-redirect/15");
-        let resp = relative_redirect(req);
-        assert!(resp.is_ok());
-        let resp = resp.unwrap();
-        assert_eq!(resp.get_status(), StatusCode::NOT_FOUND);
-        assert_eq!(resp.get_content_type(), Some(mime::TEXT_HTML_UTF_8));
+        let resp = relative_redirect(req, &Params::from([("n", "15")]));
+        assert!(resp.is_err());
+        assert_eq!(resp.unwrap_err().status(), StatusCode::NOT_FOUND);
     }
 
     #[test]
     fn test_redirect() {
         let req = &Request::from_client()
             .with_path("/redirect/5");
-        let resp = redirect(req);
+        let resp = redirect(req, &Params::from([("n", "5")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::FOUND);
@@ -1,46 +1,90 @@
 use fastly::http::{StatusCode, Version};
-use fastly::{Error, mime, Request, Response};
+use fastly::{mime, Request, Response};
+use crate::error::Error;
 use serde_json::{json, to_string_pretty};
-use serde::{Deserialize};
+use crate::client_hello;
+use crate::compat;
+use crate::format;
+use crate::forwarded;
+use crate::geo;
+use crate::router::Params;
+use crate::user_agent;
 use crate::utils::req_headers;
 
 #[utoipa::path(
     get,
     path = "/user-agent",
     tag = "Request inspection",
+    params(
+        ("parse" = Option<bool>, Query, description = "Whether to break the User-Agent down, as /user-agent/parsed does"),
+    ),
     responses(
-        (status = 200, description = "The request’s User-Agent header.", content_type = "application/json")
+        (status = 200, description = "The request’s User-Agent header.", content_type = "application/json"),
+        (status = 400, description = "The User-Agent header is not valid ASCII, or is missing when parsing.", content_type = "application/problem+json"),
     )
 )]
 /// Return the incoming requests's User-Agent header.
-pub fn user_agent(req: &Request) -> Result<Response, Error> {
-    let ua = req.get_header("user-agent").unwrap().to_str().unwrap();
+pub fn user_agent(req: &Request, params: &Params) -> Result<Response, Error> {
+    if req.get_query_parameter("parse").is_some_and(|v| v == "true" || v == "1") {
+        return user_agent_parsed(req, params);
+    }
+    let ua = req_user_agent(req)?;
     let resp = json!({
             "user-agent": ua
         });
 
-    Ok(Response::from_status(StatusCode::OK)
-        .with_content_type(mime::APPLICATION_JSON)
-        .with_body(to_string_pretty(&resp).unwrap()))
+    format::echo(req, &resp)
+}
+
+#[utoipa::path(
+    get,
+    path = "/user-agent/parsed",
+    tag = "Request inspection",
+    responses(
+        (status = 200, description = "The request’s User-Agent broken down into browser, engine, OS, device class and bot, and checked against the RFC 9110 syntax.", content_type = "application/json"),
+        (status = 400, description = "No User-Agent header, or one that is not valid ASCII.", content_type = "application/problem+json"),
+    )
+)]
+/// Return the incoming request's User-Agent header, parsed.
+pub fn user_agent_parsed(req: &Request, _: &Params) -> Result<Response, Error> {
+    let ua = req_user_agent(req)?
+        .ok_or_else(|| Error::BadRequest(String::from("No User-Agent header")))?;
+
+    format::echo(req, &user_agent::parse(ua))
+}
+
+fn req_user_agent(req: &Request) -> Result<Option<&str>, Error> {
+    req.get_header("user-agent")
+        .map(|ua| ua.to_str())
+        .transpose()
+        .map_err(|_| Error::BadRequest(String::from("User-Agent header is not valid ASCII")))
 }
 
 #[utoipa::path(
     get,
     path = "/ip",
     tag = "Request inspection",
+    params(
+        ("geo" = Option<bool>, Query, description = "Whether to add the geolocation of the address"),
+    ),
     responses(
         (status = 200, description = "The Requester's IP address", content_type = "application/json")
     )
 )]
-/// Returns the requester's IP Address.
-pub fn ip(req: &Request) -> Result<Response, Error> {
-    let resp = json!({
-            "ip": req.get_client_ip_addr()
+/// Returns the requester's IP Address, and the proxy chain it claims to have gone through.
+pub fn ip(req: &Request, _: &Params) -> Result<Response, Error> {
+    if compat::enabled(req) {
+        return format::echo(req, &json!({"origin": compat::origin(req)}));
+    }
+    let mut resp = json!({
+            "ip": req.get_client_ip_addr(),
+            "proxy_chain": forwarded::analyze(req),
         });
+    if req.get_query_parameter("geo").is_some_and(|v| v == "true" || v == "1") {
+        resp["geo"] = json!(req.get_client_ip_addr().and_then(geo::lookup));
+    }
 
-    Ok(Response::from_status(StatusCode::OK)
-        .with_content_type(mime::APPLICATION_JSON)
-        .with_body(to_string_pretty(&resp).unwrap()))
+    format::echo(req, &resp)
 }
 
 #[utoipa::path(
@@ -52,14 +96,24 @@ pub fn ip(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Return the incoming request's HTTP headers
-pub fn headers(req: &Request) -> Result<Response, Error> {
+pub fn headers(req: &Request, _: &Params) -> Result<Response, Error> {
     let resp = json!({
             "headers": req_headers(req),
         });
 
-    Ok(Response::from_status(StatusCode::OK)
-        .with_content_type(mime::APPLICATION_JSON)
-        .with_body(to_string_pretty(&resp).unwrap_or_default()))
+    format::echo(req, &resp)
+}
+
+/// The request's HTTP version, e.g. `1.1` or `2`.
+pub fn version_name(req: &Request) -> &'static str {
+    match req.get_version() {
+        Version::HTTP_09 => "0.9",
+        Version::HTTP_10 => "1.0",
+        Version::HTTP_11 => "1.1",
+        Version::HTTP_2 => "2",
+        Version::HTTP_3 => "3",
+        _ => "unknown",
+    }
 }
 
 #[utoipa::path(
@@ -73,14 +127,7 @@ pub fn headers(req: &Request) -> Result<Response, Error> {
 /// Return the incoming request's HTTP headers
 pub fn http_version(req: &Request) -> Result<Response, Error> {
     let resp = json!({
-            "http_version": match req.get_version() {
-        Version::HTTP_09 => "0.9",
-        Version::HTTP_10 => "1.0",
-        Version::HTTP_11 => "1.1",
-        Version::HTTP_2 => "2",
-        Version::HTTP_3 => "3",
-        _ => "unknown",
-    }
+            "http_version": version_name(req)
         });
 
     Ok(Response::from_status(StatusCode::OK)
@@ -98,7 +145,7 @@ pub fn http_version(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Return the incoming request's HTTP version
-pub fn http_version_get(req: &Request) -> Result<Response, Error> {
+pub fn http_version_get(req: &Request, _: &Params) -> Result<Response, Error> {
     http_version(req)
 }
 
@@ -111,7 +158,7 @@ pub fn http_version_get(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Return the incoming request's HTTP version
-pub fn http_version_post(req: &Request) -> Result<Response, Error> {
+pub fn http_version_post(req: &Request, _: &Params) -> Result<Response, Error> {
     http_version(req)
 }
 
@@ -124,7 +171,7 @@ pub fn http_version_post(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Return the incoming request's HTTP version
-pub fn http_version_put(req: &Request) -> Result<Response, Error> {
+pub fn http_version_put(req: &Request, _: &Params) -> Result<Response, Error> {
     http_version(req)
 }
 
@@ -137,7 +184,7 @@ pub fn http_version_put(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Return the incoming request's HTTP version
-pub fn http_version_patch(req: &Request) -> Result<Response, Error> {
+pub fn http_version_patch(req: &Request, _: &Params) -> Result<Response, Error> {
     http_version(req)
 }
 
@@ -150,13 +197,55 @@ pub fn http_version_patch(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Return the incoming request's HTTP version
-pub fn http_version_delete(req: &Request) -> Result<Response, Error> {
+pub fn http_version_delete(req: &Request, _: &Params) -> Result<Response, Error> {
     http_version(req)
 }
 
+/// The ALPN protocol the connection most likely settled on, inferred from the
+/// HTTP version since Fastly does not expose the negotiated one. HTTP/2 and
+/// HTTP/3 cannot run without theirs, HTTP/1.1 only used ALPN if the client
+/// offered it.
+fn inferred_alpn(req: &Request, offered: &[String]) -> Option<&'static str> {
+    match req.get_version() {
+        Version::HTTP_2 => Some("h2"),
+        Version::HTTP_3 => Some("h3"),
+        Version::HTTP_11 if offered.iter().any(|p| p == "http/1.1") => Some("http/1.1"),
+        _ => None,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/tls",
+    tag = "Request inspection",
+    responses(
+        (status = 200, description = "The client's TLS connection and fingerprints. Fields Fastly does not expose for the connection, as on plain HTTP or under Viceroy, are null.", content_type = "application/json")
+    )
+)]
+/// Return the TLS parameters and fingerprints of the client connection.
+pub fn tls(req: &Request, _: &Params) -> Result<Response, Error> {
+    let hello = req.get_tls_client_hello().and_then(client_hello::parse);
+    let offered = hello.as_ref().map(|h| h.alpn.clone());
+    let resp = json!({
+            "protocol": req.get_tls_protocol(),
+            "cipher": req.get_tls_cipher_openssl_name(),
+            "sni": hello.and_then(|h| h.server_name),
+            "alpn": {
+                "offered": offered,
+                "inferred_from_version": offered.as_ref().and_then(|o| inferred_alpn(req, o)),
+            },
+            "ja3": req.get_tls_ja3_md5().map(|md5| md5.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+            "ja4": req.get_tls_ja4(),
+            "h2_fingerprint": req.get_client_h2_fingerprint(),
+        });
+
+    format::echo(req, &resp)
+}
+
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
+    use serde::Deserialize;
     use super::*;
 
     #[test]
@@ -170,7 +259,7 @@ mod test {
         let req = &Request::from_client()
             .with_header("user-agent", "Microsoft Explorer 6")
             .with_path("/user-agent");
-        let resp = user_agent(req);
+        let resp = user_agent(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -184,6 +273,30 @@ mod test {
         assert_eq!(m, expect);
     }
 
+    #[test]
+    fn test_user_agent_missing() {
+        let req = &Request::get("http://example.com/user-agent");
+        let resp = user_agent(req, &Params::default()).unwrap();
+        let v: serde_json::Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["user-agent"], serde_json::Value::Null);
+
+        assert!(matches!(user_agent_parsed(req, &Params::default()), Err(Error::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_user_agent_parsed() {
+        let req = &Request::from_client()
+            .with_header("user-agent", "Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/115.0")
+            .with_path("/user-agent")
+            .with_query_str("parse=true");
+        let resp = user_agent(req, &Params::default()).unwrap();
+        let v: serde_json::Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["browser"]["family"], "Firefox");
+        assert_eq!(v["os"]["family"], "Linux");
+        assert_eq!(v["device_class"], "desktop");
+        assert_eq!(v["well_formed"], true);
+    }
+
     #[test]
     fn test_ip_success() {
         #[derive(Debug, Deserialize, PartialEq)]
@@ -193,7 +306,7 @@ mod test {
 
         let req = &Request::from_client()
             .with_path("/ip");
-        let resp = ip(req);
+        let resp = ip(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -207,6 +320,43 @@ mod test {
         assert_eq!(m, expect);
     }
 
+    #[test]
+    fn test_ip_geo() {
+        let req = &Request::from_client()
+            .with_path("/ip")
+            .with_query_str("geo=true");
+        let resp = ip(req, &Params::default()).unwrap();
+        let v: serde_json::Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v["geo"]["country_code"], "CA");
+    }
+
+    #[test]
+    fn test_inferred_alpn() {
+        let offered = vec![String::from("h2"), String::from("http/1.1")];
+        let req = Request::get("http://example.com/tls");
+        assert_eq!(inferred_alpn(&req, &offered), Some("http/1.1"));
+        assert_eq!(inferred_alpn(&req, &[]), None);
+        let mut req = req;
+        req.set_version(Version::HTTP_2);
+        assert_eq!(inferred_alpn(&req, &offered), Some("h2"));
+    }
+
+    #[test]
+    fn test_tls_plain_http() {
+        let req = &Request::get("http://example.com/tls");
+        let resp = tls(req, &Params::default()).unwrap();
+        let v: serde_json::Value = serde_json::from_str(resp.into_body_str().as_str()).unwrap();
+        assert_eq!(v, json!({
+            "protocol": null,
+            "cipher": null,
+            "sni": null,
+            "alpn": {"offered": null, "inferred_from_version": null},
+            "ja3": null,
+            "ja4": null,
+            "h2_fingerprint": null,
+        }));
+    }
+
     #[test]
     fn test_headers_success() {
         #[derive(Debug, Deserialize, PartialEq)]
@@ -218,7 +368,7 @@ mod test {
             .with_header("foo", "bar")
             .with_header("bee", "baz")
             .with_path("/headers");
-        let resp = headers(req);
+        let resp = headers(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
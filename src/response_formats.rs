@@ -1,7 +1,28 @@
 use fastly::http::StatusCode;
-use fastly::{Error, mime, Request, Response};
+use fastly::{mime, Request, Response};
+use crate::compat;
+use crate::error::Error;
+use crate::router::Params;
 use crate::utils::req_to_json;
+use crate::timing::{metric, SERVER_TIMING};
 use deflate::{deflate_bytes, deflate_bytes_gzip};
+use serde_json::{json, to_string_pretty};
+use std::time::Instant;
+
+/// The echo compressed by /brotli, /deflate and /gzip. In httpbin
+/// compatibility mode, it is httpbin's, flagged with `flag`.
+fn compressed_echo(req: &Request, flag: &str) -> String {
+    if !compat::enabled(req) {
+        return req_to_json(req);
+    }
+    let resp = json!({
+        flag: true,
+        "headers": compat::headers(req),
+        "method": req.get_method_str(),
+        "origin": compat::origin(req),
+    });
+    to_string_pretty(&resp).unwrap_or_default()
+}
 
 #[utoipa::path(
     get,
@@ -12,17 +33,17 @@ use deflate::{deflate_bytes, deflate_bytes_gzip};
     )
 )]
 /// Returns Brotli-encoded data.
-pub fn brotli(req: &Request) -> Result<Response, Error> {
-    let res = req_to_json(req);
-    let mut enc = vec!();
+pub fn brotli(req: &Request, _: &Params) -> Result<Response, Error> {
+    let res = compressed_echo(req, "brotli");
+    let start = Instant::now();
+    let mut enc = vec![];
     let params = brotli::enc::BrotliEncoderParams::default();
-    match brotli::BrotliCompress(&mut res.as_bytes(), &mut enc, &params) {
-        Ok(_) => {},
-        Err(e) => panic!("Error {:?}", e),
-    }
-    return Ok(Response::from_status(StatusCode::OK)
+    brotli::BrotliCompress(&mut res.as_bytes(), &mut enc, &params)
+        .map_err(|e| Error::Internal(format!("Brotli compression failed: {}", e)))?;
+    Ok(Response::from_status(StatusCode::OK)
         .with_content_type(mime::APPLICATION_JSON)
         .with_header("content-encoding", "br")
+        .with_header(SERVER_TIMING, metric("compress", start.elapsed()))
         .with_body(enc))
 }
 
@@ -35,12 +56,14 @@ pub fn brotli(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns Deflate-encoded data.
-pub fn deflate(req: &Request) -> Result<Response, Error> {
-    let res = req_to_json(req);
+pub fn deflate(req: &Request, _: &Params) -> Result<Response, Error> {
+    let res = compressed_echo(req, "deflated");
+    let start = Instant::now();
     let enc = deflate_bytes(res.as_bytes());
-    return Ok(Response::from_status(StatusCode::OK)
+    Ok(Response::from_status(StatusCode::OK)
         .with_content_type(mime::APPLICATION_JSON)
         .with_header("content-encoding", "deflate")
+        .with_header(SERVER_TIMING, metric("compress", start.elapsed()))
         .with_body(enc))
 }
 
@@ -53,12 +76,14 @@ pub fn deflate(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns GZip-encoded data.
-pub fn gzip(req: &Request) -> Result<Response, Error> {
-    let res = req_to_json(req);
+pub fn gzip(req: &Request, _: &Params) -> Result<Response, Error> {
+    let res = compressed_echo(req, "gzipped");
+    let start = Instant::now();
     let enc = deflate_bytes_gzip(res.as_bytes());
-    return Ok(Response::from_status(StatusCode::OK)
+    Ok(Response::from_status(StatusCode::OK)
         .with_content_type(mime::APPLICATION_JSON)
         .with_header("content-encoding", "gzip")
+        .with_header(SERVER_TIMING, metric("compress", start.elapsed()))
         .with_body(enc))
 }
 
@@ -71,8 +96,8 @@ pub fn gzip(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns a simple HTML document.
-pub fn html(_: &Request) -> Result<Response, Error> {
-    return crate::assets::serve("html.html", mime::TEXT_HTML);
+pub fn html(_: &Request, _: &Params) -> Result<Response, Error> {
+    crate::assets::serve("html.html", mime::TEXT_HTML)
 }
 
 #[utoipa::path(
@@ -84,8 +109,8 @@ pub fn html(_: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns a simple JSON document.
-pub fn json(_: &Request) -> Result<Response, Error> {
-    return crate::assets::serve("json.json", mime::APPLICATION_JSON);
+pub fn json(_: &Request, _: &Params) -> Result<Response, Error> {
+    crate::assets::serve("json.json", mime::APPLICATION_JSON)
 }
 
 #[utoipa::path(
@@ -97,8 +122,8 @@ pub fn json(_: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns some robots.txt rules.
-pub fn robots_txt(_: &Request) -> Result<Response, Error> {
-    return crate::assets::serve("robots.txt", mime::TEXT_PLAIN);
+pub fn robots_txt(_: &Request, _: &Params) -> Result<Response, Error> {
+    crate::assets::serve("robots.txt", mime::TEXT_PLAIN)
 }
 
 #[utoipa::path(
@@ -110,7 +135,7 @@ pub fn robots_txt(_: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns a simple XML document.
-pub fn xml(_: &Request) -> Result<Response, Error> {
+pub fn xml(_: &Request, _: &Params) -> Result<Response, Error> {
     let mime_xml: mime::Mime = "application/xml".parse().unwrap_or(mime::APPLICATION_OCTET_STREAM);
     crate::assets::serve("xml.xml", mime_xml)
 }
@@ -124,7 +149,7 @@ pub fn xml(_: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns page denied by robots.txt rules.
-pub fn deny(_: &Request) -> Result<Response, Error> {
+pub fn deny(_: &Request, _: &Params) -> Result<Response, Error> {
     crate::assets::serve("deny.txt", mime::TEXT_PLAIN)
 }
 
@@ -137,10 +162,23 @@ pub fn deny(_: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns a UTF-8 encoded body.
-pub fn encoding_utf8(_: &Request) -> Result<Response, Error> {
+pub fn encoding_utf8(_: &Request, _: &Params) -> Result<Response, Error> {
     crate::assets::serve("utf8.txt", mime::TEXT_PLAIN)
 }
 
+#[utoipa::path(
+    get,
+    path = "/utf8",
+    tag = "Response formats",
+    responses(
+        (status = 200, description = "Encoded UTF-8 content.", body=str, content_type = "text/plain")
+    )
+)]
+/// Returns a UTF-8 encoded body. Same as /encoding/utf8.
+pub fn utf8(req: &Request, params: &Params) -> Result<Response, Error> {
+    encoding_utf8(req, params)
+}
+
 
 
 #[cfg(test)]
@@ -152,7 +190,7 @@ mod tests {
         let req = &Request::from_client()
             .with_path("/deflate?foo=bar");
 
-        let resp = deflate(req);
+        let resp = deflate(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
 
@@ -161,12 +199,28 @@ mod tests {
         assert_eq!(resp.get_header("content-encoding").unwrap(), "deflate");
     }
 
+    #[test]
+    fn test_gzip_compat() {
+        let req = &Request::from_client()
+            .with_header(compat::COMPAT_HEADER, "1")
+            .with_header("user-agent", "curl")
+            .with_path("/gzip");
+
+        let resp = gzip(req, &Params::default()).unwrap();
+        let mut body = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&resp.into_body_bytes()[..]), &mut body).unwrap();
+        let v: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(v["gzipped"], true);
+        assert_eq!(v["method"], "GET");
+        assert_eq!(v["headers"]["User-Agent"], "curl");
+    }
+
     #[test]
     fn test_json() {
         let req = &Request::from_client()
             .with_path("/json");
 
-        let resp = json(req);
+        let resp = json(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
 
@@ -181,7 +235,7 @@ mod tests {
             .with_header("accept", "*/*")
             .with_path("/xml");
 
-        let resp = xml(req);
+        let resp = xml(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
 
@@ -196,7 +250,7 @@ mod tests {
             .with_header("accept", "*/*")
             .with_path("/robots");
 
-        let resp = robots_txt(req);
+        let resp = robots_txt(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
 
@@ -211,7 +265,7 @@ mod tests {
             .with_header("accept", "text/plain")
             .with_path("/deny");
 
-        let resp = deny(req);
+        let resp = deny(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
 
@@ -1,7 +1,8 @@
+use fastly::http::header::HeaderName;
 use fastly::http::{HeaderValue, StatusCode};
-use regex_lite::Regex;
-use fastly::{Error, mime, Request, Response};
-use std::collections::HashMap;
+use fastly::{mime, Request, Response};
+use crate::error::Error;
+use crate::router::Params;
 
 #[utoipa::path(
     get,
@@ -15,17 +16,13 @@ use std::collections::HashMap;
     )
 )]
 // Sets a cache-control header for n seconds
-pub fn cache_value(req: &Request) -> Result<Response, Error> {
-    let caps = Regex::new(r"/cache/(\d{1,2})$")?
-        .captures(req.get_path());
-    if let Some(caps) = caps {
-        let cache_value = caps.get(1).map(|m| m.as_str()).unwrap();
-        return Ok(Response::from_status(StatusCode::OK)
-            .with_header("Cache-Control", format!("public, max-age={}", cache_value))
-            .with_content_type(mime::APPLICATION_JSON));
-    }
+pub fn cache_value(_: &Request, params: &Params) -> Result<Response, Error> {
+    let cache_value = params.parse::<u8>("value")
+        .filter(|v| *v < 100)
+        .ok_or_else(|| Error::NotFound(String::from("Cache duration must be between 0 and 99 seconds")))?;
 
-    Ok(Response::from_status(StatusCode::NOT_FOUND)
+    Ok(Response::from_status(StatusCode::OK)
+        .with_header("Cache-Control", format!("public, max-age={}", cache_value))
         .with_content_type(mime::APPLICATION_JSON))
 }
 
@@ -41,16 +38,19 @@ pub fn cache_value(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns a set of response headers from the query string
-pub fn response_headers_get(req: &Request) -> Result<Response, Error> {
-    let arg_pairs: Vec<(String, String)> = req.get_query()?;
-    let args: HashMap<&str, &str> = arg_pairs.iter().map(|m| (m.0.as_str(), m.1.as_str()))
-        .collect();
+pub fn response_headers_get(req: &Request, _: &Params) -> Result<Response, Error> {
+    let arg_pairs: Vec<(String, String)> = req.get_query()
+        .map_err(|e| Error::BadRequest(format!("Invalid query string: {}", e)))?;
 
     let mut resp =  Response::from_status(StatusCode::OK)
         .with_content_type(mime::APPLICATION_JSON);
 
-    for (k, v) in args {
-        resp = resp.with_header(k, v);
+    // Repeated parameters are returned as repeated header lines
+    for (k, v) in arg_pairs {
+        let (name, value) = HeaderName::from_bytes(k.as_bytes()).ok()
+            .zip(HeaderValue::from_str(&v).ok())
+            .ok_or_else(|| Error::BadRequest(format!("Invalid header: {}={}", k, v)))?;
+        resp.append_header(name, value);
     }
 
     Ok(resp)
@@ -69,15 +69,16 @@ pub fn response_headers_get(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Returns a set of response headers from the query string
-pub fn response_headers_post(req: &Request) -> Result<Response, Error> {
-    response_headers_get(req)
+pub fn response_headers_post(req: &Request, params: &Params) -> Result<Response, Error> {
+    response_headers_get(req, params)
 }
 
 #[utoipa::path(
     get,
-    path = "/etag",
+    path = "/etag/{etag}",
     tag = "Response inspection",
     params(
+        ("etag" = String, Path, description = "ETag of the resource"),
         ("if-none-match" = String, Header),
         ("if-match" = String, Header),
     ),
@@ -87,42 +88,36 @@ pub fn response_headers_post(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Assumes the resource has the given etag and responds to If-None-Match and If-Match headers appropriately.
-pub fn etag(req: &Request) -> Result<Response, Error> {
-    let caps = Regex::new(r"/etag/(\w+)$")?
-        .captures(req.get_path());
-    if let Some(caps) = caps {
-        let etag = caps.get(1).map_or("404", |m| m.as_str());
-        let d = HeaderValue::from_static("");
-        let if_none_match: Vec<&str> = req
-            .get_header("if-none-match")
-            .unwrap_or(&d)
-            .to_str()
-            .unwrap_or_default()
-            .split(",")
-            .collect();
-        if if_none_match.contains(&etag) || if_none_match.contains(&"*"){
-            return Ok(Response::from_status(StatusCode::NOT_MODIFIED)
-                .with_content_type(mime::APPLICATION_JSON));
-        }
-
-        let if_match: Vec<&str> = req
-            .get_header("if-none-match")
-            .unwrap_or(&d)
-            .to_str()
-            .unwrap_or_default()
-            .split(",")
-            .collect();
-        if !if_match.contains(&etag) && !if_match.contains(&"*"){
-            return Ok(Response::from_status(StatusCode::PRECONDITION_FAILED)
-                .with_content_type(mime::APPLICATION_JSON));
-        }
-
-        return Ok(Response::from_status(StatusCode::OK)
-            .with_header("ETag", etag)
+pub fn etag(req: &Request, params: &Params) -> Result<Response, Error> {
+    let etag = params.get("etag")
+        .ok_or_else(|| Error::NotFound(String::from("Missing etag")))?;
+    let d = HeaderValue::from_static("");
+    let if_none_match: Vec<&str> = req
+        .get_header("if-none-match")
+        .unwrap_or(&d)
+        .to_str()
+        .unwrap_or_default()
+        .split(",")
+        .collect();
+    if if_none_match.contains(&etag) || if_none_match.contains(&"*"){
+        return Ok(Response::from_status(StatusCode::NOT_MODIFIED)
             .with_content_type(mime::APPLICATION_JSON));
     }
 
-    Ok(Response::from_status(StatusCode::NOT_FOUND)
+    let if_match: Vec<&str> = req
+        .get_header("if-none-match")
+        .unwrap_or(&d)
+        .to_str()
+        .unwrap_or_default()
+        .split(",")
+        .collect();
+    if !if_match.contains(&etag) && !if_match.contains(&"*"){
+        return Ok(Response::from_status(StatusCode::PRECONDITION_FAILED)
+            .with_content_type(mime::APPLICATION_JSON));
+    }
+
+    Ok(Response::from_status(StatusCode::OK)
+        .with_header("ETag", etag)
         .with_content_type(mime::APPLICATION_JSON))
 }
 
@@ -136,7 +131,7 @@ mod test {
     fn test_cache_value() {
         let req = &Request::from_client()
             .with_path("/cache/23");
-        let resp = cache_value(req);
+        let resp = cache_value(req, &Params::from([("value", "23")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -149,7 +144,7 @@ mod test {
         let req = &Request::from_client()
             .with_query_str("foo=bar&fud=baz")
             .with_path("/response-headers");
-        let resp = response_headers_get(req);
+        let resp = response_headers_get(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -164,7 +159,7 @@ mod test {
             .with_method(http::Method::POST)
             .with_query_str("foo=bar&fud=baz")
             .with_path("/response-headers");
-        let resp = response_headers_post(req);
+        let resp = response_headers_post(req, &Params::default());
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
@@ -172,4 +167,22 @@ mod test {
         assert_eq!(resp.get_header_str("foo"), Some("bar"));
         assert_eq!(resp.get_header_str("fud"), Some("baz"));
     }
+
+    #[test]
+    fn test_repeated_response_headers() {
+        let req = &Request::from_client()
+            .with_query_str("X=a&X=b")
+            .with_path("/response-headers");
+        let resp = response_headers_get(req, &Params::default()).unwrap();
+        assert_eq!(resp.get_header_all_str("x"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_invalid_response_headers() {
+        let req = &Request::get("http://example.com/response-headers?a%20b=1");
+        assert!(matches!(response_headers_get(req, &Params::default()), Err(Error::BadRequest(_))));
+
+        let req = &Request::get("http://example.com/response-headers?a=1%0A2");
+        assert!(matches!(response_headers_get(req, &Params::default()), Err(Error::BadRequest(_))));
+    }
 }
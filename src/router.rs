@@ -0,0 +1,367 @@
+use fastly::http::Method;
+use fastly::{Request, Response};
+use crate::error::Error;
+use std::str::FromStr;
+use utoipa::openapi::path::{HttpMethod, Operation};
+
+/// Builds the endpoint list from handlers documented with `#[utoipa::path]`.
+/// Each handler is routed at its documented path and methods, so the router
+/// and the OpenAPI document cannot disagree. Handlers marked `any` accept every
+/// method, and their trailing path parameter matches the rest of the path.
+macro_rules! endpoints {
+    ($($kind:ident($module:ident::$handler:ident) $($any:ident)?),* $(,)?) => {
+        paste::paste! {
+            vec![$(
+                $crate::router::Endpoint::from_path::<$module::[<__path_ $handler>]>(
+                    $crate::router::ReqHandler::$kind($module::$handler),
+                    endpoints!(@any $($any)?),
+                )
+            ),*]
+        }
+    };
+    (@any) => { false };
+    (@any any) => { true };
+}
+
+#[derive(Clone, Copy)]
+pub enum ReqHandler {
+    MutHandler(fn(&mut Request, &Params) -> Result<Response, Error>),
+    Handler(fn(&Request, &Params) -> Result<Response, Error>),
+}
+
+impl ReqHandler {
+    pub fn call(&self, req: &mut Request, params: &Params) -> Result<Response, Error> {
+        match self {
+            ReqHandler::MutHandler(cb) => cb(req, params),
+            ReqHandler::Handler(cb) => cb(req, params),
+        }
+    }
+}
+
+/// A documented handler, see `endpoints!`.
+pub struct Endpoint {
+    pub handler: ReqHandler,
+    pub any_method: bool,
+    pub path: String,
+    pub methods: Vec<HttpMethod>,
+    pub operation: Operation,
+}
+
+impl Endpoint {
+    pub fn from_path<P: utoipa::Path>(handler: ReqHandler, any_method: bool) -> Self {
+        Endpoint {
+            handler,
+            any_method,
+            path: P::path(),
+            methods: P::methods(),
+            operation: P::operation(),
+        }
+    }
+}
+
+fn to_method(method: &HttpMethod) -> Method {
+    match method {
+        HttpMethod::Get => Method::GET,
+        HttpMethod::Post => Method::POST,
+        HttpMethod::Put => Method::PUT,
+        HttpMethod::Delete => Method::DELETE,
+        HttpMethod::Options => Method::OPTIONS,
+        HttpMethod::Head => Method::HEAD,
+        HttpMethod::Patch => Method::PATCH,
+        HttpMethod::Trace => Method::TRACE,
+    }
+}
+
+enum Segment {
+    Static(String),
+    Param(String),
+    /// `{name...}`, matches the rest of the path. Only valid as the last segment.
+    Rest(String),
+}
+
+struct Route {
+    /// None accepts any method, including extension methods such as PROPFIND.
+    method: Option<Method>,
+    segments: Vec<Segment>,
+    handler: ReqHandler,
+}
+
+/// Named path parameters extracted by the router, e.g. `n` in `/delay/{n}`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    /// Returns the raw value of the path parameter `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the path parameter `name` parsed as the integer `T`, or None
+    /// if it is missing or is not an integer, see `parse_integer`.
+    pub fn parse<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name).and_then(parse_integer)
+    }
+}
+
+/// Parses a path segment as an unsigned integer, written as the OpenAPI
+/// schema documents it: ASCII digits only, with no sign and no leading zero.
+pub fn parse_integer<T: FromStr>(segment: &str) -> Option<T> {
+    let digits = !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit());
+    if !digits || (segment.len() > 1 && segment.starts_with('0')) {
+        return None;
+    }
+    segment.parse::<T>().ok()
+}
+
+impl<const N: usize> From<[(&str, &str); N]> for Params {
+    fn from(params: [(&str, &str); N]) -> Self {
+        Params(params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+}
+
+/// Outcome of looking up a request in the route table.
+pub enum Match<'a> {
+    /// A handler is registered for the path and method. HEAD requests are
+    /// given the GET handler when no HEAD route exists.
+    Found(&'a ReqHandler, Params),
+    /// OPTIONS request for a known path, with the methods it supports.
+    Options(Vec<Method>),
+    /// The path exists but not for this method.
+    MethodNotAllowed(Vec<Method>),
+    NotFound,
+}
+
+/// A route table compiled once from patterns such as `/cookies/set/{name}/{value}`.
+/// Matching is done segment by segment, a `{param}` matches any non-empty segment
+/// and a trailing `{param...}` matches one or more segments.
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+fn compile(pattern: &str) -> Vec<Segment> {
+    pattern
+        .trim_start_matches('/')
+        .split('/')
+        .map(|s| match s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(name) => match name.strip_suffix("...") {
+                Some(name) => Segment::Rest(name.to_string()),
+                None => Segment::Param(name.to_string()),
+            },
+            None => Segment::Static(s.to_string()),
+        })
+        .collect()
+}
+
+fn match_segments(segments: &[Segment], path: &str) -> Option<Params> {
+    let mut parts = path.trim_start_matches('/').split('/');
+    let mut params = Params::default();
+    for segment in segments {
+        match (segment, parts.next()) {
+            (Segment::Static(s), Some(part)) if *s == part => {},
+            (Segment::Param(name), Some(part)) if !part.is_empty() => params.0.push((name.clone(), part.to_string())),
+            (Segment::Rest(name), Some(part)) => {
+                let rest = parts.by_ref().fold(part.to_string(), |acc, p| acc + "/" + p);
+                params.0.push((name.clone(), rest));
+            },
+            _ => return None,
+        }
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some(params)
+}
+
+impl Router {
+    pub fn new(routes: Vec<(Method, &str, ReqHandler)>) -> Self {
+        Router {
+            routes: routes
+                .into_iter()
+                .map(|(method, pattern, handler)| Route { method: Some(method), segments: compile(pattern), handler })
+                .collect(),
+        }
+    }
+
+    /// Registers a route that accepts any method.
+    pub fn any(mut self, pattern: &str, handler: ReqHandler) -> Self {
+        self.routes.push(Route { method: None, segments: compile(pattern), handler });
+        self
+    }
+
+    pub fn from_endpoints(endpoints: &[Endpoint]) -> Self {
+        let routes = endpoints.iter()
+            .filter(|e| !e.any_method)
+            .flat_map(|e| e.methods.iter().map(move |m| (to_method(m), e.path.as_str(), e.handler)))
+            .collect();
+
+        endpoints.iter()
+            .filter(|e| e.any_method)
+            .fold(Router::new(routes), |router, e| match e.path.strip_suffix('}') {
+                Some(path) => router.any(&format!("{}...}}", path), e.handler),
+                None => router.any(&e.path, e.handler),
+            })
+    }
+
+    /// Methods and patterns of all routes, None standing for any method.
+    #[cfg(test)]
+    pub fn patterns(&self) -> Vec<(Option<Method>, String)> {
+        self.routes.iter()
+            .map(|r| {
+                let pattern = r.segments.iter().map(|s| match s {
+                    Segment::Static(s) => s.clone(),
+                    Segment::Param(name) => format!("{{{}}}", name),
+                    Segment::Rest(name) => format!("{{{}}}", name),
+                }).collect::<Vec<String>>().join("/");
+                (r.method.clone(), format!("/{}", pattern))
+            })
+            .collect()
+    }
+
+    /// Looks up the handler registered for the method and path, along with
+    /// the path parameters it captured.
+    pub fn find(&self, method: &Method, path: &str) -> Match<'_> {
+        let mut matches: Vec<(&Route, Params)> = self.routes
+            .iter()
+            .filter_map(|r| match_segments(&r.segments, path).map(|p| (r, p)))
+            .collect();
+        if matches.is_empty() {
+            return Match::NotFound;
+        }
+
+        let allowed = allowed_methods(&matches);
+        if let Some(i) = matches.iter().position(|(r, _)| r.method.as_ref().is_none_or(|m| m == method)) {
+            let (r, params) = matches.swap_remove(i);
+            return Match::Found(&r.handler, params);
+        }
+
+        match *method {
+            Method::HEAD => match matches.into_iter().find(|(r, _)| r.method == Some(Method::GET)) {
+                Some((r, params)) => Match::Found(&r.handler, params),
+                None => Match::MethodNotAllowed(allowed),
+            },
+            Method::OPTIONS => Match::Options(allowed),
+            _ => Match::MethodNotAllowed(allowed),
+        }
+    }
+}
+
+/// Methods accepted for a path: the registered ones, HEAD wherever GET is
+/// registered, and OPTIONS.
+fn allowed_methods(matches: &[(&Route, Params)]) -> Vec<Method> {
+    let mut allowed: Vec<Method> = vec![];
+    let mut add = |m: &Method| if !allowed.contains(m) { allowed.push(m.clone()) };
+    for method in matches.iter().filter_map(|(r, _)| r.method.as_ref()) {
+        add(method);
+        if method == Method::GET {
+            add(&Method::HEAD);
+        }
+    }
+    add(&Method::OPTIONS);
+    allowed
+}
+
+/// Formats methods as the value of an `Allow` header.
+pub fn allow_header(methods: &[Method]) -> String {
+    methods.iter().map(Method::as_str).collect::<Vec<&str>>().join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fastly::http::StatusCode;
+    use ReqHandler::*;
+
+    fn ok(_: &Request, _: &Params) -> Result<Response, Error> {
+        Ok(Response::from_status(StatusCode::OK))
+    }
+
+    fn found(m: Match) -> Option<Params> {
+        match m {
+            Match::Found(_, params) => Some(params),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_params() {
+        let router = Router::new(vec![
+            (Method::GET, "/cookies/set/{name}/{value}", Handler(ok)),
+        ]);
+
+        let params = found(router.find(&Method::GET, "/cookies/set/foo/bar")).unwrap();
+        assert_eq!(params, Params::from([("name", "foo"), ("value", "bar")]));
+        assert!(matches!(router.find(&Method::GET, "/cookies/set/foo"), Match::NotFound));
+        assert!(matches!(router.find(&Method::GET, "/cookies/set/foo/"), Match::NotFound));
+    }
+
+    #[test]
+    fn test_typed_params() {
+        let params = Params::from([("n", "42"), ("s", "foo")]);
+        assert_eq!(params.parse::<u16>("n"), Some(42));
+        assert_eq!(params.parse::<u16>("s"), None);
+        assert_eq!(params.parse::<u16>("missing"), None);
+
+        let params = Params::from([("plus", "+4"), ("zero", "04"), ("minus", "-4"), ("space", " 4")]);
+        assert_eq!(params.parse::<u16>("plus"), None);
+        assert_eq!(params.parse::<u16>("zero"), None);
+        assert_eq!(params.parse::<i32>("minus"), None);
+        assert_eq!(params.parse::<u16>("space"), None);
+        assert_eq!(parse_integer::<u16>("0"), Some(0));
+    }
+
+    #[test]
+    fn test_static_routes() {
+        let router = Router::new(vec![
+            (Method::GET, "/get", Handler(ok)),
+            (Method::GET, "/swagger.json", Handler(ok)),
+        ]);
+
+        assert!(found(router.find(&Method::GET, "/get")).is_some());
+        assert!(found(router.find(&Method::GET, "/get/")).is_none());
+        assert!(found(router.find(&Method::GET, "/foo/swagger.json")).is_none());
+        assert!(found(router.find(&Method::GET, "/swagger.json")).is_some());
+    }
+
+    #[test]
+    fn test_any_method_and_rest() {
+        let router = Router::new(vec![])
+            .any("/anything", Handler(ok))
+            .any("/anything/{path...}", Handler(ok));
+
+        let propfind = Method::from_bytes(b"PROPFIND").unwrap();
+        assert_eq!(found(router.find(&propfind, "/anything")), Some(Params::default()));
+        assert_eq!(found(router.find(&Method::DELETE, "/anything/a/b/c")), Some(Params::from([("path", "a/b/c")])));
+        assert!(found(router.find(&Method::GET, "/anythings")).is_none());
+    }
+
+    #[test]
+    fn test_method_not_allowed() {
+        let router = Router::new(vec![
+            (Method::GET, "/status/{codes}", Handler(ok)),
+            (Method::POST, "/status/{codes}", Handler(ok)),
+        ]);
+
+        match router.find(&Method::PUT, "/status/200") {
+            Match::MethodNotAllowed(allowed) => assert_eq!(allow_header(&allowed), "GET, HEAD, POST, OPTIONS"),
+            _ => panic!("expected a 405"),
+        }
+    }
+
+    #[test]
+    fn test_head_and_options() {
+        let router = Router::new(vec![
+            (Method::GET, "/get", Handler(ok)),
+            (Method::DELETE, "/delete", Handler(ok)),
+        ]);
+
+        assert!(found(router.find(&Method::HEAD, "/get")).is_some());
+        assert!(matches!(router.find(&Method::HEAD, "/delete"), Match::MethodNotAllowed(_)));
+        match router.find(&Method::OPTIONS, "/delete") {
+            Match::Options(allowed) => assert_eq!(allow_header(&allowed), "DELETE, OPTIONS"),
+            _ => panic!("expected the allowed methods"),
+        }
+    }
+}
@@ -1,20 +1,17 @@
 use fastly::http::StatusCode;
-use fastly::{Error, mime, Request, Response};
-use regex_lite::Regex;
+use fastly::{mime, Request, Response};
+use crate::error::Error;
+use crate::router::{parse_integer, Params};
 use rand::seq::SliceRandom;
 
-fn rr_http_statuses(req: &Request) -> Result<Response, Error> {
-    let caps = Regex::new(r"/status/((\d{3},?)+)$")?
-        .captures(req.get_path());
-    if let Some(caps) = caps {
-        let statuses:Vec<&str> = caps.get(1).map_or("404", |m| m.as_str()).split(",").collect();
+fn rr_http_statuses(params: &Params) -> Result<Response, Error> {
+    let codes = params.get("codes").unwrap_or_default();
+    let statuses:Vec<&str> = codes.split(',').collect();
+    let status = statuses.choose(&mut rand::thread_rng()).and_then(|s| parse_integer::<u16>(s)).unwrap_or_default();
+    let status = StatusCode::from_u16(status)
+        .map_err(|_| Error::NotFound(format!("Invalid status code in {}", codes)))?;
 
-        let status = statuses.choose(&mut rand::thread_rng()).unwrap().parse::<u16>().unwrap_or(404);
-        return Ok(Response::from_status(StatusCode::from_u16(status).unwrap_or(StatusCode::NOT_FOUND))
-            .with_content_type(mime::TEXT_HTML_UTF_8));
-    }
-
-    Ok(Response::from_status(StatusCode::NOT_FOUND)
+    Ok(Response::from_status(status)
         .with_content_type(mime::TEXT_HTML_UTF_8))
 }
 
@@ -35,8 +32,8 @@ fn rr_http_statuses(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Return status code or random status code if more than one is given
-pub fn get(req: &Request) -> Result<Response, Error> {
-    rr_http_statuses(req)
+pub fn get(_: &Request, params: &Params) -> Result<Response, Error> {
+    rr_http_statuses(params)
 }
 
 #[utoipa::path(
@@ -56,8 +53,8 @@ pub fn get(req: &Request) -> Result<Response, Error> {
     )
 )]
 /// Return status code or random status code if more than one is given
-pub fn post(req: &mut Request) -> Result<Response, Error> {
-    rr_http_statuses(req)
+pub fn post(_: &mut Request, params: &Params) -> Result<Response, Error> {
+    rr_http_statuses(params)
 }
 
 #[utoipa::path(
@@ -77,8 +74,8 @@ pub fn post(req: &mut Request) -> Result<Response, Error> {
     )
 )]
 /// Return status code or random status code if more than one is given
-pub fn put(req: &mut Request) -> Result<Response, Error> {
-    rr_http_statuses(req)
+pub fn put(_: &mut Request, params: &Params) -> Result<Response, Error> {
+    rr_http_statuses(params)
 }
 
 #[utoipa::path(
@@ -98,8 +95,8 @@ pub fn put(req: &mut Request) -> Result<Response, Error> {
     )
 )]
 /// Return status code or random status code if more than one is given
-pub fn patch(req: &mut Request) -> Result<Response, Error> {
-    rr_http_statuses(req)
+pub fn patch(_: &mut Request, params: &Params) -> Result<Response, Error> {
+    rr_http_statuses(params)
 }
 
 #[utoipa::path(
@@ -119,8 +116,8 @@ pub fn patch(req: &mut Request) -> Result<Response, Error> {
     )
 )]
 /// Return status code or random status code if more than one is given
-pub fn delete(req: &Request) -> Result<Response, Error> {
-    rr_http_statuses(req)
+pub fn delete(_: &Request, params: &Params) -> Result<Response, Error> {
+    rr_http_statuses(params)
 }
 
 
@@ -131,18 +128,26 @@ mod test {
     fn test_get_200() {
         let req = &Request::from_client()
             .with_path("/status/200");
-        let resp = get(req);
+        let resp = get(req, &Params::from([("codes", "200")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::OK);
         assert_eq!(resp.get_content_type(), Some(mime::TEXT_HTML_UTF_8));
     }
 
+    #[test]
+    fn test_get_signed() {
+        let req = &Request::from_client()
+            .with_path("/status/+200");
+        let resp = get(req, &Params::from([("codes", "+200")]));
+        assert!(matches!(resp, Err(Error::NotFound(_))));
+    }
+
     #[test]
     fn test_get_500() {
         let req = &Request::from_client()
             .with_path("/status/500");
-        let resp = get(req);
+        let resp = get(req, &Params::from([("codes", "500")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::INTERNAL_SERVER_ERROR);
@@ -153,7 +158,7 @@ mod test {
     fn test_post_302() {
         let mut req = Request::from_client()
             .with_path("/status/302");
-        let resp = post(&mut req);
+        let resp = post(&mut req, &Params::from([("codes", "302")]));
         assert!(resp.is_ok());
         let resp = resp.unwrap();
         assert_eq!(resp.get_status(), StatusCode::FOUND);
@@ -164,10 +169,8 @@ mod test {
     fn test_post_non_existing() {
         let mut req = Request::from_client()
             .with_path("/status/9999");
-        let resp = post(&mut req);
-        assert!(resp.is_ok());
-        let resp = resp.unwrap();
-        assert_eq!(resp.get_status(), StatusCode::NOT_FOUND);
-        assert_eq!(resp.get_content_type(), Some(mime::TEXT_HTML_UTF_8));
+        let resp = post(&mut req, &Params::from([("codes", "9999")]));
+        assert!(resp.is_err());
+        assert_eq!(resp.unwrap_err().status(), StatusCode::NOT_FOUND);
     }
 }
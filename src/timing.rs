@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+pub const SERVER_TIMING: &str = "server-timing";
+
+/// Formats a Server-Timing metric, with the duration in milliseconds.
+pub fn metric(name: &str, duration: Duration) -> String {
+    format!("{};dur={:.3}", name, duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_metric() {
+        assert_eq!(metric("route", Duration::from_micros(1500)), "route;dur=1.500");
+    }
+}
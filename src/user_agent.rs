@@ -0,0 +1,340 @@
+use serde_json::{json, Value};
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// Recognises a client when `token` is in the User-Agent. Its version follows
+/// `version`, which is often the token itself; an empty `version` means the
+/// User-Agent carries none.
+struct Rule {
+    family: &'static str,
+    token: &'static str,
+    version: &'static str,
+}
+
+const fn rule(family: &'static str, token: &'static str, version: &'static str) -> Rule {
+    Rule { family, token, version }
+}
+
+/// Browsers and HTTP libraries. Browsers built on others also announce the
+/// ones they are built on, so they come first: Edge says it is Chrome, and
+/// Chrome says it is Safari.
+const BROWSERS: [Rule; 27] = [
+    rule("Edge", "Edg/", "Edg/"),
+    rule("Edge", "EdgA/", "EdgA/"),
+    rule("Edge", "EdgiOS/", "EdgiOS/"),
+    rule("Edge", "Edge/", "Edge/"),
+    rule("Opera", "OPR/", "OPR/"),
+    rule("Opera", "Opera", "Version/"),
+    rule("Samsung Internet", "SamsungBrowser/", "SamsungBrowser/"),
+    rule("Yandex Browser", "YaBrowser/", "YaBrowser/"),
+    rule("Vivaldi", "Vivaldi/", "Vivaldi/"),
+    rule("Headless Chrome", "HeadlessChrome/", "HeadlessChrome/"),
+    rule("Chrome", "CriOS/", "CriOS/"),
+    rule("Firefox", "FxiOS/", "FxiOS/"),
+    rule("Firefox", "Firefox/", "Firefox/"),
+    rule("Chromium", "Chromium/", "Chromium/"),
+    rule("Chrome", "Chrome/", "Chrome/"),
+    rule("Internet Explorer", "MSIE ", "MSIE "),
+    rule("Internet Explorer", "Trident/", "rv:"),
+    rule("Safari", "Safari/", "Version/"),
+    rule("curl", "curl/", "curl/"),
+    rule("Wget", "Wget/", "Wget/"),
+    rule("Python Requests", "python-requests/", "python-requests/"),
+    rule("Python urllib", "Python-urllib/", "Python-urllib/"),
+    rule("Go HTTP client", "Go-http-client/", "Go-http-client/"),
+    rule("OkHttp", "okhttp/", "okhttp/"),
+    rule("Postman", "PostmanRuntime/", "PostmanRuntime/"),
+    rule("axios", "axios/", "axios/"),
+    rule("node-fetch", "node-fetch/", "node-fetch/"),
+];
+
+/// Rendering engines. Blink browsers claim AppleWebKit, and WebKit ones claim
+/// to be "like Gecko".
+const ENGINES: [Rule; 6] = [
+    rule("EdgeHTML", "Edge/", "Edge/"),
+    rule("Blink", "Chrome/", "Chrome/"),
+    rule("WebKit", "AppleWebKit/", "AppleWebKit/"),
+    rule("Trident", "Trident/", "Trident/"),
+    rule("Presto", "Presto/", "Presto/"),
+    rule("Gecko", "Gecko/", "rv:"),
+];
+
+/// Operating systems. iOS claims to be "like Mac OS X", and Android and
+/// Chrome OS are Linux.
+const OPERATING_SYSTEMS: [Rule; 10] = [
+    rule("Windows Phone", "Windows Phone ", "Windows Phone "),
+    rule("Windows", "Windows NT ", "Windows NT "),
+    rule("iOS", "iPhone OS ", "iPhone OS "),
+    rule("iOS", "CPU OS ", "CPU OS "),
+    rule("Android", "Android", "Android "),
+    rule("Chrome OS", "CrOS ", ""),
+    rule("macOS", "Mac OS X", "Mac OS X "),
+    rule("FreeBSD", "FreeBSD", ""),
+    rule("Linux", "Linux", ""),
+    rule("Windows", "Windows", ""),
+];
+
+/// Crawlers known by name.
+const BOTS: [Rule; 12] = [
+    rule("Googlebot", "Googlebot", "Googlebot/"),
+    rule("Bingbot", "bingbot", "bingbot/"),
+    rule("Yahoo! Slurp", "Slurp", ""),
+    rule("DuckDuckBot", "DuckDuckBot", "DuckDuckBot/"),
+    rule("Baiduspider", "Baiduspider", "Baiduspider/"),
+    rule("YandexBot", "YandexBot", "YandexBot/"),
+    rule("Applebot", "Applebot", "Applebot/"),
+    rule("AhrefsBot", "AhrefsBot", "AhrefsBot/"),
+    rule("GPTBot", "GPTBot", "GPTBot/"),
+    rule("Facebook", "facebookexternalhit", "facebookexternalhit/"),
+    rule("Twitterbot", "Twitterbot", "Twitterbot/"),
+    rule("LinkedInBot", "LinkedInBot", "LinkedInBot/"),
+];
+
+/// Substrings found in the User-Agent of crawlers and other automated clients.
+const BOT_MARKERS: [&str; 8] = ["bot", "crawler", "spider", "slurp", "curl", "wget", "headless", "python-requests"];
+
+/// Device classes, recognised by the first of their markers in the User-Agent.
+/// Consoles and TVs run desktop or mobile systems, so they come first.
+const DEVICE_CLASSES: [(&str, &[&str]); 4] = [
+    ("console", &["PlayStation", "Xbox", "Nintendo"]),
+    ("tv", &["SmartTV", "SMART-TV", "AppleTV", "CrKey", "BRAVIA"]),
+    ("tablet", &["iPad", "Tablet"]),
+    ("mobile", &["Mobi", "iPhone", "iPod", "Windows Phone"]),
+];
+
+/// Whether the User-Agent belongs to a crawler or another automated client.
+pub fn is_bot(user_agent: &str) -> bool {
+    let lowercase = user_agent.to_ascii_lowercase();
+    find(&BOTS, user_agent).is_some() || BOT_MARKERS.iter().any(|marker| lowercase.contains(marker))
+}
+
+/// The version following `prefix`, e.g. `118.0.0.0` after `Chrome/`.
+/// Underscores, as in `Mac OS X 10_15_7`, become dots.
+fn version_after(user_agent: &str, prefix: &str) -> Option<String> {
+    if prefix.is_empty() {
+        return None;
+    }
+    let (_, rest) = user_agent.split_once(prefix)?;
+    let version: String = rest.chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_'))
+        .map(|c| if c == '_' { '.' } else { c })
+        .collect();
+    (!version.is_empty()).then_some(version)
+}
+
+/// The family and version of the first rule matching the User-Agent.
+fn find(rules: &[Rule], user_agent: &str) -> Option<(&'static str, Option<String>)> {
+    rules.iter()
+        .find(|r| user_agent.contains(r.token))
+        .map(|r| (r.family, version_after(user_agent, r.version)))
+}
+
+fn to_value(found: Option<(&str, Option<String>)>) -> Value {
+    let (family, version) = found.unzip();
+    json!({"family": family, "version": version.flatten()})
+}
+
+fn device_class(user_agent: &str, os: Option<&str>) -> &'static str {
+    if is_bot(user_agent) {
+        return "bot";
+    }
+    if let Some((class, _)) = DEVICE_CLASSES.iter().find(|(_, markers)| markers.iter().any(|m| user_agent.contains(m))) {
+        return class;
+    }
+    match os {
+        // Android phones say Mobile, tablets don't
+        Some("Android") => "tablet",
+        Some("Windows" | "macOS" | "Linux" | "Chrome OS" | "FreeBSD") => "desktop",
+        _ => "unknown",
+    }
+}
+
+fn is_tchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+fn read_token(chars: &mut Peekable<CharIndices>) -> String {
+    let mut token = String::new();
+    while let Some((_, c)) = chars.next_if(|&(_, c)| is_tchar(c)) {
+        token.push(c);
+    }
+    token
+}
+
+/// Splits the User-Agent into its products and comments following the
+/// RFC 9110 grammar, `product *( RWS ( product / comment ) )`.
+fn tokenize(user_agent: &str) -> Result<(Vec<Value>, Vec<String>), String> {
+    let mut products = vec![];
+    let mut comments = vec![];
+    let mut chars = user_agent.char_indices().peekable();
+
+    if user_agent.is_empty() {
+        return Err(String::from("The User-Agent is empty"));
+    }
+    while let Some(&(start, c)) = chars.peek() {
+        if c == '(' {
+            if products.is_empty() {
+                return Err(String::from("The User-Agent must start with a product"));
+            }
+            let mut depth = 0;
+            let mut end = None;
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => { chars.next(); },
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {},
+                }
+                if depth == 0 {
+                    end = Some(i);
+                    break;
+                }
+            }
+            let end = end.ok_or_else(|| format!("Unterminated comment at {}", start))?;
+            comments.push(user_agent[start + 1..end].trim().to_string());
+        } else if is_tchar(c) {
+            let name = read_token(&mut chars);
+            let version = match chars.next_if(|&(_, c)| c == '/') {
+                Some(_) => match read_token(&mut chars) {
+                    version if version.is_empty() => return Err(format!("Product {} has an empty version", name)),
+                    version => Some(version),
+                },
+                None => None,
+            };
+            products.push(json!({"name": name, "version": version}));
+        } else {
+            return Err(format!("Unexpected character {:?} at {}", c, start));
+        }
+
+        match chars.peek() {
+            None => break,
+            Some(&(_, ' ' | '\t')) => {},
+            Some(&(i, _)) => return Err(format!("Missing whitespace at {}", i)),
+        }
+        while chars.next_if(|&(_, c)| c == ' ' || c == '\t').is_some() {}
+        if chars.peek().is_none() {
+            return Err(String::from("The User-Agent ends with whitespace"));
+        }
+    }
+
+    Ok((products, comments))
+}
+
+/// Breaks a User-Agent into browser, engine, OS, device class and bot, using
+/// the rules above rather than the platform's device detection. Also checks
+/// that it follows the RFC 9110 syntax: the products and comments are null
+/// when it doesn't, and `error` says why.
+pub fn parse(user_agent: &str) -> Value {
+    let os = find(&OPERATING_SYSTEMS, user_agent);
+    let (products, comments, error) = match tokenize(user_agent) {
+        Ok((products, comments)) => (Some(products), Some(comments), None),
+        Err(e) => (None, None, Some(e)),
+    };
+    json!({
+        "user_agent": user_agent,
+        "browser": to_value(find(&BROWSERS, user_agent)),
+        "engine": to_value(find(&ENGINES, user_agent)),
+        "device_class": device_class(user_agent, os.as_ref().map(|(family, _)| *family)),
+        "os": to_value(os),
+        "is_bot": is_bot(user_agent),
+        "bot": find(&BOTS, user_agent).map(|bot| to_value(Some(bot))),
+        "well_formed": error.is_none(),
+        "error": error,
+        "products": products,
+        "comments": comments,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CHROME_WINDOWS: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/118.0.0.0 Safari/537.36";
+    const SAFARI_IPHONE: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1";
+    const FIREFOX_LINUX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/115.0";
+    const EDGE_MAC: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/118.0.0.0 Safari/537.36 Edg/118.0.2088.46";
+    const CHROME_TABLET: &str = "Mozilla/5.0 (Linux; Android 13; SM-X700) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/118.0.0.0 Safari/537.36";
+    const GOOGLEBOT: &str = "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)";
+
+    #[test]
+    fn test_is_bot() {
+        assert!(is_bot(GOOGLEBOT));
+        assert!(is_bot("curl/8.4.0"));
+        assert!(!is_bot(FIREFOX_LINUX));
+    }
+
+    #[test]
+    fn test_version_after() {
+        assert_eq!(version_after(CHROME_WINDOWS, "Chrome/"), Some(String::from("118.0.0.0")));
+        assert_eq!(version_after(EDGE_MAC, "Mac OS X "), Some(String::from("10.15.7")));
+        assert_eq!(version_after(CHROME_WINDOWS, "Firefox/"), None);
+        assert_eq!(version_after(CHROME_WINDOWS, ""), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let v = parse(CHROME_WINDOWS);
+        assert_eq!(v["browser"], json!({"family": "Chrome", "version": "118.0.0.0"}));
+        assert_eq!(v["engine"], json!({"family": "Blink", "version": "118.0.0.0"}));
+        assert_eq!(v["os"], json!({"family": "Windows", "version": "10.0"}));
+        assert_eq!(v["device_class"], "desktop");
+        assert_eq!(v["is_bot"], false);
+        assert_eq!(v["bot"], Value::Null);
+
+        let v = parse(SAFARI_IPHONE);
+        assert_eq!(v["browser"], json!({"family": "Safari", "version": "17.0"}));
+        assert_eq!(v["engine"]["family"], "WebKit");
+        assert_eq!(v["os"], json!({"family": "iOS", "version": "17.0"}));
+        assert_eq!(v["device_class"], "mobile");
+
+        let v = parse(FIREFOX_LINUX);
+        assert_eq!(v["browser"], json!({"family": "Firefox", "version": "115.0"}));
+        assert_eq!(v["engine"], json!({"family": "Gecko", "version": "109.0"}));
+        assert_eq!(v["os"], json!({"family": "Linux", "version": null}));
+
+        assert_eq!(parse(EDGE_MAC)["browser"]["family"], "Edge");
+        assert_eq!(parse(EDGE_MAC)["os"]["family"], "macOS");
+        assert_eq!(parse(CHROME_TABLET)["device_class"], "tablet");
+        assert_eq!(parse(CHROME_TABLET)["os"], json!({"family": "Android", "version": "13"}));
+
+        let v = parse(GOOGLEBOT);
+        assert_eq!(v["device_class"], "bot");
+        assert_eq!(v["bot"], json!({"family": "Googlebot", "version": "2.1"}));
+        assert_eq!(v["browser"]["family"], Value::Null);
+
+        let v = parse("my-sdk/1.2.3 curl/8.4.0");
+        assert_eq!(v["browser"], json!({"family": "curl", "version": "8.4.0"}));
+        assert_eq!(v["device_class"], "bot");
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let (products, comments) = tokenize(FIREFOX_LINUX).unwrap();
+        assert_eq!(products, vec![
+            json!({"name": "Mozilla", "version": "5.0"}),
+            json!({"name": "Gecko", "version": "20100101"}),
+            json!({"name": "Firefox", "version": "115.0"}),
+        ]);
+        assert_eq!(comments, vec!["X11; Linux x86_64; rv:109.0"]);
+
+        let (products, comments) = tokenize(r"my-sdk/1.0 (nested (comment) \) ok) python").unwrap();
+        assert_eq!(products[1], json!({"name": "python", "version": null}));
+        assert_eq!(comments, vec![r"nested (comment) \) ok"]);
+
+        assert!(tokenize("").is_err());
+        assert!(tokenize("(comment) sdk/1.0").is_err());
+        assert!(tokenize("sdk/ 1.0").is_err());
+        assert!(tokenize("sdk/1.0(comment)").is_err());
+        assert!(tokenize("sdk/1.0 (unterminated").is_err());
+        assert!(tokenize("sdk/1.0 ").is_err());
+        assert!(tokenize("sdk@1.0").is_err());
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let v = parse("sdk/1.0 (unterminated");
+        assert_eq!(v["well_formed"], false);
+        assert_eq!(v["error"], "Unterminated comment at 8");
+        assert_eq!(v["products"], Value::Null);
+    }
+}
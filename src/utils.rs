@@ -1,63 +1,262 @@
-use fastly::http::Method;
+use base64::{Engine as _, engine::general_purpose};
+use fastly::http::header;
+use fastly::mime::{self, Mime};
 use fastly::Request;
-use std::collections::HashMap;
-use serde_json::{json, to_string_pretty};
+use sha2::{Digest, Sha256};
+use serde_json::map::Entry;
+use serde_json::{json, to_string_pretty, Map, Value};
+use crate::config::CONFIG;
+use crate::error::Error;
+use crate::decompress;
+use crate::forwarded;
+use crate::middleware;
+use crate::multipart;
 
+pub const X_REQUEST_ID: &str = "x-request-id";
 
-pub fn req_headers(req: &Request) -> HashMap<&str, &str> {
-    return req.get_headers()
-        .map(|m| (m.0.as_str(), m.1.to_str().unwrap_or("")))
-        .collect();
+/// Collects key/value pairs into a JSON object, httpbin-style: a key seen once
+/// maps to its value, a repeated key to the array of its values, in order.
+pub fn multi_map<K: Into<String>>(pairs: impl IntoIterator<Item = (K, Value)>) -> Map<String, Value> {
+    let mut map = Map::new();
+    for (k, v) in pairs {
+        match map.entry(k) {
+            Entry::Vacant(e) => { e.insert(v); },
+            Entry::Occupied(mut e) => match e.get_mut() {
+                Value::Array(values) => values.push(v),
+                first => *first = Value::Array(vec![first.take(), v]),
+            },
+        }
+    }
+    map
 }
 
-pub fn req_to_json(req: &Request) -> String {
+pub fn req_headers(req: &Request) -> Map<String, Value> {
+    multi_map(req.get_headers().map(|(k, v)| (k.as_str(), json!(v.to_str().unwrap_or("")))))
+}
+
+/// Parses the request's Content-Type, None when missing or malformed.
+pub fn media_type(req: &Request) -> Option<Mime> {
+    req.get_header(header::CONTENT_TYPE)
+        .and_then(|t| t.to_str().ok())
+        .and_then(|t| t.parse::<Mime>().ok())
+}
+
+/// Whether the media type is `application/json` or a `+json` structured syntax suffix type.
+fn is_json(media_type: &Mime) -> bool {
+    media_type.type_() == mime::APPLICATION
+        && (media_type.subtype() == mime::JSON || media_type.suffix() == Some(mime::JSON))
+}
+
+/// The SHA-256 digest of `bytes`, in lowercase hex.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Describes an uploaded file: its name, type, size, SHA-256 and content, as
+/// text when it is valid UTF-8 and as a base64 data URI otherwise.
+fn file_to_value(part: multipart::Part) -> Value {
+    let content_type = part.content_type.unwrap_or_else(|| mime::APPLICATION_OCTET_STREAM.to_string());
+    let sha256 = sha256_hex(&part.body);
+    let size = part.body.len();
+    let content = String::from_utf8(part.body).unwrap_or_else(|e| {
+        format!("data:{};base64,{}", content_type, general_purpose::STANDARD.encode(e.as_bytes()))
+    });
+
+    json!({
+        "filename": part.filename,
+        "content_type": content_type,
+        "size": size,
+        "sha256": sha256,
+        "content": content,
+    })
+}
+
+/// Fields echoed for every request: args, headers, origin, request_id and url,
+/// plus proxy_chain when the request went through proxies.
+pub fn req_to_value(req: &Request) -> Value {
     let arg_pairs: Vec<(String, String)> = req.get_query().unwrap_or_default();
-    let args: HashMap<&str, &str> = arg_pairs.iter().map(|m| (m.0.as_str(), m.1.as_str()))
-        .collect();
+    let args = multi_map(arg_pairs.into_iter().map(|(k, v)| (k, json!(v))));
 
-    let resp = json!({
+    let mut value = json!({
         "args": args,
         "headers": req_headers(req),
         "origin": req.get_client_ip_addr(),
+        "request_id": middleware::request_id(),
         "url": req.get_url_str()
     });
+    if forwarded::has_forwarding_headers(req) {
+        value["proxy_chain"] = forwarded::analyze(req);
+    }
+    value
+}
 
-    return to_string_pretty(&resp).unwrap();
+pub fn req_to_json(req: &Request) -> String {
+    to_string_pretty(&req_to_value(req)).unwrap_or_default()
 }
 
-pub fn req_with_body_to_json(req: &mut Request) -> String {
-    let arg_pairs: Vec<(String, String)> = req.get_query().unwrap_or_default();
-    let args: HashMap<&str, &str> = arg_pairs.iter().map(|m| (m.0.as_str(), m.1.as_str()))
-        .collect();
-
-    let resp = match *req.get_method() {
-        Method::POST => {
-            let f: Vec<(String, String)>;
-            let mut fo: HashMap<&str, &str> = HashMap::new();
-
-            if req.get_header("content-type").unwrap() == "application/x-www-form-urlencoded" {
-                f = req.take_body_form::<Vec<(String, String)>>().unwrap();
-                fo = f
-                    .iter()
-                    .map(|m| (m.0.as_str(), m.1.as_str()))
-                    .collect();
-            }
+/// Echoes the request along with its body, whatever the method, in httpbin's
+/// shape: `data` holds the raw body, `form` the urlencoded fields, `json` the
+/// parsed JSON body and `files` the uploaded files. A JSON body that does not
+/// parse is described in `json_error`. Compressed bodies are decoded first, and
+/// the codings removed are listed in `decoded_from`, as in Content-Encoding.
+pub fn req_with_body_to_value(req: &mut Request) -> Result<Value, Error> {
+    let mut resp = req_to_value(req);
+    if let Some(encodings) = decompress::decode_body(req, CONFIG.limits.max_body_bytes)? {
+        resp["decoded_from"] = json!(encodings.join(", "));
+    }
+    let mut data = String::new();
+    let mut form: Vec<(String, Value)> = vec![];
+    let mut files: Vec<(String, Value)> = vec![];
+    let mut json = Value::Null;
 
-            json!({
-                "args": args,
-                "form": fo,
-                "headers": req_headers(req),
-                "origin": req.get_client_ip_addr(),
-                "url": req.get_url_str()
-            })
+    match media_type(req) {
+        Some(t) if t.essence_str() == mime::APPLICATION_WWW_FORM_URLENCODED.essence_str() => {
+            form = req.take_body_form::<Vec<(String, String)>>()
+                .map_err(|e| Error::BadRequest(format!("Invalid form body: {}", e)))?
+                .into_iter()
+                .map(|(k, v)| (k, json!(v)))
+                .collect();
         },
-        _ => json!({
-            "args": args,
-            "headers": req_headers(req),
-            "origin": req.get_client_ip_addr(),
-            "url": req.get_url_str()
-        }),
-    };
-
-    return to_string_pretty(&resp).unwrap_or_default();
+        Some(t) if t.essence_str() == mime::MULTIPART_FORM_DATA.essence_str() => {
+            let boundary = t.get_param(mime::BOUNDARY)
+                .ok_or_else(|| Error::BadRequest(String::from("Invalid multipart body: no boundary")))?;
+            for part in multipart::parse(&req.take_body_bytes(), boundary.as_str())? {
+                match part.filename {
+                    Some(_) => files.push((part.name.clone(), file_to_value(part))),
+                    None => form.push((part.name, json!(String::from_utf8_lossy(&part.body)))),
+                }
+            }
+        },
+        Some(t) if is_json(&t) => {
+            data = req.take_body_str_lossy();
+            match serde_json::from_str(&data) {
+                Ok(v) => json = v,
+                Err(e) => resp["json_error"] = json!({
+                    "message": e.to_string(),
+                    "line": e.line(),
+                    "column": e.column(),
+                }),
+            }
+        },
+        _ => data = req.take_body_str_lossy(),
+    }
+
+    resp["data"] = json!(data);
+    resp["files"] = Value::Object(multi_map(files));
+    resp["form"] = Value::Object(multi_map(form));
+    resp["json"] = json;
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_multi_map() {
+        let map = multi_map([("a", json!("1")), ("b", json!("2")), ("a", json!("3")), ("a", json!("4"))]);
+        assert_eq!(Value::Object(map), json!({"a": ["1", "3", "4"], "b": "2"}));
+    }
+
+    #[test]
+    fn test_repeated_values() {
+        let mut req = Request::post("http://example.com/post?a=1&a=2&b=3")
+            .with_header("content-type", "application/x-www-form-urlencoded")
+            .with_header("accept", "text/html")
+            .with_body("f=1&f=2");
+        req.append_header("accept", "application/json");
+        let v = req_with_body_to_value(&mut req).unwrap();
+        assert_eq!(v["args"], json!({"a": ["1", "2"], "b": "3"}));
+        assert_eq!(v["form"], json!({"f": ["1", "2"]}));
+        assert_eq!(v["headers"]["accept"], json!(["text/html", "application/json"]));
+    }
+
+    #[test]
+    fn test_compressed_body() {
+        let mut enc = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        std::io::Write::write_all(&mut enc, br#"{"foo": "bar"}"#).unwrap();
+        let mut req = Request::post("http://example.com/post")
+            .with_header("content-type", "application/json")
+            .with_header("content-encoding", "gzip")
+            .with_body(enc.finish().unwrap());
+        let v = req_with_body_to_value(&mut req).unwrap();
+        assert_eq!(v["decoded_from"], "gzip");
+        assert_eq!(v["json"]["foo"], "bar");
+    }
+
+    #[test]
+    fn test_is_json() {
+        let is_json = |t: &str| is_json(&t.parse::<Mime>().unwrap());
+        assert!(is_json("application/json"));
+        assert!(is_json("Application/JSON; charset=utf-8"));
+        assert!(is_json("application/problem+json"));
+        assert!(is_json("application/vnd.api+json"));
+        assert!(!is_json("application/jsonp"));
+        assert!(!is_json("text/plain"));
+    }
+
+    #[test]
+    fn test_media_type() {
+        let req = Request::post("http://example.com/post")
+            .with_header("content-type", "application/json; charset=utf-8");
+        assert_eq!(media_type(&req).map(|t| t.essence_str().to_string()), Some(String::from("application/json")));
+        let req = Request::post("http://example.com/post")
+            .with_header("content-type", fastly::http::HeaderValue::from_bytes(b"text/\xff").unwrap());
+        assert_eq!(media_type(&req), None);
+        assert_eq!(media_type(&Request::post("http://example.com/post")), None);
+    }
+
+    #[test]
+    fn test_form_charset() {
+        let mut req = Request::post("http://example.com/post")
+            .with_header("content-type", "application/x-www-form-urlencoded; charset=utf-8")
+            .with_body("foo=bar");
+        let v = req_with_body_to_value(&mut req).unwrap();
+        assert_eq!(v["form"]["foo"], "bar");
+    }
+
+    #[test]
+    fn test_multipart_body() {
+        let body = [
+            &b"--XyZ\r\nContent-Disposition: form-data; name=\"comment\"\r\n\r\nhello\r\n"[..],
+            b"--XyZ\r\nContent-Disposition: form-data; name=\"text\"; filename=\"a.txt\"\r\n",
+            b"Content-Type: text/plain\r\n\r\nabc\r\n",
+            b"--XyZ\r\nContent-Disposition: form-data; name=\"bin\"; filename=\"a.bin\"\r\n\r\n\xff\x00\r\n",
+            b"--XyZ--\r\n",
+        ].concat();
+        let mut req = Request::post("http://example.com/post")
+            .with_header("content-type", "multipart/form-data; boundary=XyZ")
+            .with_body(body);
+        let v = req_with_body_to_value(&mut req).unwrap();
+        assert_eq!(v["form"]["comment"], "hello");
+        assert_eq!(v["files"]["text"]["filename"], "a.txt");
+        assert_eq!(v["files"]["text"]["content_type"], "text/plain");
+        assert_eq!(v["files"]["text"]["size"], 3);
+        assert_eq!(v["files"]["text"]["sha256"], "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(v["files"]["text"]["content"], "abc");
+        assert_eq!(v["files"]["bin"]["content"], "data:application/octet-stream;base64,/wA=");
+    }
+
+    #[test]
+    fn test_json_body() {
+        let mut req = Request::post("http://example.com/post")
+            .with_header("content-type", "application/vnd.api+json; charset=utf-8")
+            .with_body(r#"{"foo": [1, 2]}"#);
+        let v = req_with_body_to_value(&mut req).unwrap();
+        assert_eq!(v["json"], json!({"foo": [1, 2]}));
+        assert_eq!(v["data"], r#"{"foo": [1, 2]}"#);
+        assert!(v.get("json_error").is_none());
+    }
+
+    #[test]
+    fn test_invalid_json_body() {
+        let mut req = Request::post("http://example.com/post")
+            .with_header("content-type", "application/json")
+            .with_body("{\"foo\":");
+        let v = req_with_body_to_value(&mut req).unwrap();
+        assert_eq!(v["json"], Value::Null);
+        assert_eq!(v["data"], "{\"foo\":");
+        assert_eq!(v["json_error"]["line"], 1);
+        assert!(v["json_error"]["message"].as_str().unwrap().contains("EOF"));
+    }
 }